@@ -0,0 +1,397 @@
+use crate::db::DB_POOL;
+use crate::schedule::now;
+use crate::{ErrorString, Reply};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use std::env;
+use std::path::{Path, PathBuf};
+use teloxide::net::Download;
+use teloxide::prelude::*;
+
+const SETTINGS_VERSION: u32 = 1;
+
+/// 按用户导出的表, 均以 `telegram_id` 区分用户
+const SETTINGS_TABLES: [&str; 13] = [
+    "monitor",
+    "mute",
+    "maintenance_window",
+    "digest",
+    "digest_export",
+    "traffic_quota",
+    "expiry_setting",
+    "node_billing",
+    "exchange_rate",
+    "cost_setting",
+    "card_template",
+    "unit_setting",
+    "latency_alert",
+];
+
+/// 带有 `chat_id` 的表, 只导入发往当前会话的行, 避免借导入向任意群组发送报告
+const CHAT_TABLES: [&str; 2] = ["digest", "digest_export"];
+
+/// 自增主键在导入时重新生成
+const SKIPPED_COLUMNS: [&str; 1] = ["id"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    version: u32,
+    tables: Map<String, Value>,
+}
+
+/// Bot 管理员由配置文件中的 `admin_ids` 指定
+pub fn is_admin(user_id: i64) -> bool {
+    env::var("ADMIN_IDS").is_ok_and(|ids| {
+        ids.split(',')
+            .filter_map(|id| id.trim().parse::<i64>().ok())
+            .any(|id| id == user_id)
+    })
+}
+
+/// 使用 `VACUUM INTO` 生成一致的数据库快照, 返回文件内容与文件名
+pub async fn backup() -> Result<(Vec<u8>, String), ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let path = env::temp_dir().join(format!("komari-tgbot-{}.db", uuid::Uuid::new_v4()));
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().to_string())
+        .execute(db_pool)
+        .await
+        .map_err(|e| format!("无法生成数据库快照: {e}"))?;
+
+    let content = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("无法读取数据库快照: {e}"));
+    let _ = tokio::fs::remove_file(&path).await;
+
+    Ok((
+        content?,
+        format!("komari-tgbot-{}.db", now().format("%Y%m%d-%H%M")),
+    ))
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// `komari-tgbot restore BACKUP_FILE`, 需在 Bot 停止时运行, 原数据库保留为 `.bak` 文件
+pub async fn restore(db_file: &str, backup_file: &str) -> Result<String, ErrorString> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("sqlite:{backup_file}?mode=ro"))
+        .await
+        .map_err(|e| format!("无法打开备份文件: {e}"))?;
+
+    let integrity = sqlx::query_scalar::<_, String>("PRAGMA integrity_check")
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| format!("无法校验备份文件: {e}"))?;
+    if integrity != "ok" {
+        return Err(format!("备份文件已损坏: {integrity}"));
+    }
+
+    let monitors = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM monitor")
+        .fetch_one(&pool)
+        .await
+        .map_err(|_| String::from("备份文件不是 Komari Bot 数据库"))?;
+    pool.close().await;
+
+    let db_path = Path::new(db_file);
+    let mut lines = vec![];
+
+    if db_path.exists() {
+        let bak_path = sidecar(db_path, &format!(".bak-{}", now().format("%Y%m%d%H%M%S")));
+        for suffix in ["", "-wal", "-shm"] {
+            let from = sidecar(db_path, suffix);
+            if from.exists() {
+                tokio::fs::rename(&from, sidecar(&bak_path, suffix))
+                    .await
+                    .map_err(|e| format!("无法备份原数据库: {e}"))?;
+            }
+        }
+        lines.push(format!("原数据库已保存为 {}", bak_path.display()));
+    }
+
+    tokio::fs::copy(backup_file, db_path)
+        .await
+        .map_err(|e| format!("无法写入数据库: {e}"))?;
+
+    lines.push(format!(
+        "已从 {backup_file} 恢复数据库，包含 {monitors} 个连接"
+    ));
+    Ok(lines.join("\n"))
+}
+
+/// `/export_settings` 的 JSON 内容, 包含连接、通知令牌与各项设置
+pub async fn export_settings(telegram_id: i64) -> Result<Vec<u8>, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let mut tables = Map::new();
+
+    for table in SETTINGS_TABLES {
+        let rows = sqlx::query(&format!("SELECT * FROM {table} WHERE telegram_id = ?"))
+            .bind(telegram_id)
+            .fetch_all(db_pool)
+            .await
+            .map_err(|e| format!("无法读取 {table}: {e}"))?;
+
+        let mut values = vec![];
+        for row in rows {
+            let mut object = Map::new();
+
+            for column in row.columns() {
+                let name = column.name();
+                if name == "telegram_id" || SKIPPED_COLUMNS.contains(&name) {
+                    continue;
+                }
+
+                let raw = row
+                    .try_get_raw(column.ordinal())
+                    .map_err(|e| format!("无法读取 {table}.{name}: {e}"))?;
+                let value = if raw.is_null() {
+                    Value::Null
+                } else {
+                    match raw.type_info().name() {
+                        "INTEGER" => row.try_get::<i64, _>(column.ordinal()).map(Value::from),
+                        "REAL" => row.try_get::<f64, _>(column.ordinal()).map(Value::from),
+                        _ => row.try_get::<String, _>(column.ordinal()).map(Value::from),
+                    }
+                    .map_err(|e| format!("无法读取 {table}.{name}: {e}"))?
+                };
+
+                object.insert(name.to_string(), value);
+            }
+
+            values.push(Value::Object(object));
+        }
+
+        if !values.is_empty() {
+            tables.insert(table.to_string(), Value::Array(values));
+        }
+    }
+
+    if tables.is_empty() {
+        return Err(String::from("没有可导出的设置"));
+    }
+
+    serde_json::to_vec_pretty(&Settings {
+        version: SETTINGS_VERSION,
+        tables,
+    })
+    .map_err(|e| format!("无法生成 JSON: {e}"))
+}
+
+/// 该行是否发往 `chat_id` 以外的会话
+fn targets_other_chat(table: &str, row: &Map<String, Value>, chat_id: i64) -> bool {
+    CHAT_TABLES.contains(&table) && row.get("chat_id").and_then(Value::as_i64) != Some(chat_id)
+}
+
+/// `/import_settings`, 覆盖该用户在文件中出现的各表设置
+///
+/// 带有 `chat_id` 的表只覆盖与导入 `chat_id` 相同会话的设置
+pub async fn import_settings(
+    telegram_id: i64,
+    chat_id: i64,
+    content: &[u8],
+) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let settings: Settings =
+        serde_json::from_slice(content).map_err(|e| format!("无法解析设置文件: {e}"))?;
+    if settings.version != SETTINGS_VERSION {
+        return Err(format!("不支持的设置文件版本: {}", settings.version));
+    }
+
+    if let Some(table) = settings
+        .tables
+        .keys()
+        .find(|table| !SETTINGS_TABLES.contains(&table.as_str()))
+    {
+        return Err(format!("未知的设置表: {table}"));
+    }
+
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .map_err(|e| format!("无法开始事务: {e}"))?;
+    let mut imported = vec![];
+
+    for table in SETTINGS_TABLES {
+        let Some(rows) = settings.tables.get(table) else {
+            continue;
+        };
+        let rows = rows.as_array().ok_or(format!("{table} 的格式不正确"))?;
+
+        // 列名只取自数据库, 避免拼接任意 SQL
+        let columns: Vec<String> = sqlx::query_scalar::<_, String>(&format!(
+            "SELECT name FROM pragma_table_info('{table}')"
+        ))
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|e| format!("无法读取 {table} 的结构: {e}"))?;
+
+        let chat_table = CHAT_TABLES.contains(&table);
+        let sql = if chat_table {
+            format!("DELETE FROM {table} WHERE telegram_id = ? AND chat_id = ?")
+        } else {
+            format!("DELETE FROM {table} WHERE telegram_id = ?")
+        };
+        let mut delete = sqlx::query(&sql).bind(telegram_id);
+        if chat_table {
+            delete = delete.bind(chat_id);
+        }
+        delete
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| format!("无法清除 {table}: {e}"))?;
+
+        let mut written = 0;
+        for row in rows {
+            let object = row.as_object().ok_or(format!("{table} 的格式不正确"))?;
+            if targets_other_chat(table, object, chat_id) {
+                continue;
+            }
+
+            let fields: Vec<(&String, &Value)> = columns
+                .iter()
+                .filter(|column| column.as_str() != "telegram_id")
+                .filter(|column| !SKIPPED_COLUMNS.contains(&column.as_str()))
+                .filter_map(|column| object.get(column).map(|value| (column, value)))
+                .collect();
+
+            let names = fields
+                .iter()
+                .map(|(column, _)| column.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let placeholders = vec!["?"; fields.len()].join(", ");
+            let sql = if fields.is_empty() {
+                format!("INSERT INTO {table} (telegram_id) VALUES (?)")
+            } else {
+                format!("INSERT INTO {table} (telegram_id, {names}) VALUES (?, {placeholders})")
+            };
+
+            let mut query = sqlx::query(&sql).bind(telegram_id);
+            for (column, value) in fields {
+                query = match value {
+                    Value::Null => query.bind(None::<String>),
+                    Value::Bool(bool) => query.bind(*bool),
+                    Value::Number(number) => match number.as_i64() {
+                        Some(number) => query.bind(number),
+                        None => query.bind(number.as_f64()),
+                    },
+                    Value::String(string) => query.bind(string.clone()),
+                    _ => return Err(format!("{table}.{column} 的值不正确")),
+                };
+            }
+
+            query
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| format!("无法写入 {table}: {e}"))?;
+            written += 1;
+        }
+
+        let skipped = rows.len() - written;
+        imported.push(if skipped == 0 {
+            format!("{table}: {written} 条")
+        } else {
+            format!("{table}: {written} 条, 跳过 {skipped} 条发往其他会话的设置")
+        });
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| format!("无法提交事务: {e}"))?;
+
+    Ok(format!("已导入设置\n{}", imported.join("\n")))
+}
+
+/// `/backup`, 仅限 Bot 管理员在私聊中使用
+pub async fn backup_command(msg: &Message, user_id: i64) -> Reply {
+    if !is_admin(user_id) || !msg.chat.is_private() {
+        return Reply::Text(String::from("此命令仅限 Bot 管理员在私聊中使用"));
+    }
+
+    Reply::document(backup().await, "无法备份数据库")
+}
+
+/// `/export_settings`, 设置中包含通知令牌, 不在群组中发送
+pub async fn export_settings_command(msg: &Message, telegram_id: i64) -> Reply {
+    if !msg.chat.is_private() {
+        return Reply::Text(String::from("请在私聊中使用此命令"));
+    }
+
+    Reply::document(
+        export_settings(telegram_id)
+            .await
+            .map(|content| (content, format!("komari-tgbot-settings-{telegram_id}.json"))),
+        "无法导出设置",
+    )
+}
+
+/// `/import_settings`, 读取随命令发送或被回复的设置文件
+pub async fn import_settings_command(bot: &Bot, msg: &Message, telegram_id: i64) -> Reply {
+    if !msg.chat.is_private() {
+        return Reply::Text(String::from("请在私聊中使用此命令"));
+    }
+
+    let Some(document) = msg
+        .document()
+        .or_else(|| msg.reply_to_message().and_then(Message::document))
+    else {
+        return Reply::Text(String::from(
+            "请回复 /export_settings 导出的文件，或在发送该文件时附上 /import_settings",
+        ));
+    };
+
+    let mut content = vec![];
+    let downloaded = match bot.get_file(document.file.id.clone()).await {
+        Ok(file) => bot
+            .download_file(&file.path, &mut content)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = downloaded {
+        return Reply::Text(format!("无法下载设置文件: {e}"));
+    }
+
+    Reply::text(
+        import_settings(telegram_id, msg.chat.id.0, &content).await,
+        "无法导入设置",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn rows_for_other_chats_are_not_imported() {
+        let own = row(serde_json::json!({ "chat_id": 42, "format": "csv" }));
+        let group = row(serde_json::json!({ "chat_id": -1001, "format": "csv" }));
+        let missing = row(serde_json::json!({ "format": "csv" }));
+
+        assert!(!targets_other_chat("digest_export", &own, 42));
+        assert!(targets_other_chat("digest_export", &group, 42));
+        assert!(targets_other_chat("digest", &missing, 42));
+        assert!(!targets_other_chat("mute", &group, 42));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::ErrorString;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use std::fmt::Write;
+
+/// 回调数据格式版本, 格式变化时递增, 旧版本按钮提示过期
+pub const CALLBACK_VERSION: u8 = 1;
+
+/// Telegram 回调数据上限
+const MAX_CALLBACK_LEN: usize = 64;
+
+/// 签名截取的字节数, 以十六进制编码
+const SIGNATURE_LEN: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackAction {
+    /// 单个节点的状态卡片, 页码为标签页, 可附带分组以在组内翻页
+    Node,
+    /// `/get_node_id` 节点列表翻页
+    Nodes,
+    /// `/find` 查找结果翻页
+    Find,
+    /// `/regions` 地区列表
+    Regions,
+    /// 某个地区的节点列表
+    Region,
+}
+
+impl CallbackAction {
+    fn code(self) -> char {
+        match self {
+            Self::Node => 'n',
+            Self::Nodes => 'l',
+            Self::Find => 'f',
+            Self::Regions => 'r',
+            Self::Region => 'g',
+        }
+    }
+
+    fn from_code(code: char) -> Option<Self> {
+        match code {
+            'n' => Some(Self::Node),
+            'l' => Some(Self::Nodes),
+            'f' => Some(Self::Find),
+            'r' => Some(Self::Regions),
+            'g' => Some(Self::Region),
+            _ => None,
+        }
+    }
+}
+
+/// 按钮回调数据, 编码为 `{版本}{动作}:{签名}:{实例}:{节点}:{页码}:{参数}`
+///
+/// 实例为保存 Komari 连接的 Telegram 用户 ID, 版本与数字均以 36 进制编码, 动作为头部的最后一个字符,
+/// 配置了 `callback_secret` 时附带 HMAC-SHA256 签名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackData {
+    pub action: CallbackAction,
+    pub instance: i64,
+    pub node: Option<i32>,
+    pub page: usize,
+    pub arg: Option<String>,
+}
+
+impl CallbackData {
+    pub fn new(action: CallbackAction, instance: i64) -> Self {
+        Self {
+            action,
+            instance,
+            node: None,
+            page: 0,
+            arg: None,
+        }
+    }
+
+    pub fn node(mut self, node: i32) -> Self {
+        self.node = Some(node);
+        self
+    }
+
+    pub fn page(mut self, page: usize) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.arg = Some(arg.to_string());
+        self
+    }
+
+    pub fn encode(&self) -> String {
+        self.encode_with(secret().as_deref())
+    }
+
+    fn encode_with(&self, secret: Option<&str>) -> String {
+        let body = format!(
+            "{}:{}:{}:{}",
+            to_base36(self.instance),
+            self.node
+                .map(|node| to_base36(node.into()))
+                .unwrap_or_default(),
+            to_base36(i64::try_from(self.page).unwrap_or_default()),
+            self.arg.as_deref().unwrap_or_default()
+        );
+        let head = encode_head(CALLBACK_VERSION, self.action);
+        let signature = sign(secret, &head, &body).unwrap_or_default();
+
+        format!("{head}:{signature}:{body}")
+    }
+
+    /// 参数过长时编码会超出 64 字节, 由调用方决定省略参数或按钮
+    pub fn fits(&self) -> bool {
+        self.encode().len() <= MAX_CALLBACK_LEN
+    }
+
+    pub fn decode(data: &str) -> Result<Self, ErrorString> {
+        Self::decode_with(data, secret().as_deref())
+    }
+
+    fn decode_with(data: &str, secret: Option<&str>) -> Result<Self, ErrorString> {
+        let expired = || String::from("按钮已过期，请重新发送命令");
+
+        let mut fields = data.splitn(3, ':');
+        let head = fields.next().ok_or_else(expired)?;
+        let signature = fields.next().ok_or_else(expired)?;
+        let body = fields.next().ok_or_else(expired)?;
+
+        let (version, action) = decode_head(head).ok_or_else(expired)?;
+        if version != i64::from(CALLBACK_VERSION) {
+            return Err(expired());
+        }
+
+        if !verify(secret, head, body, signature) {
+            return Err(String::from("按钮签名无效"));
+        }
+
+        let mut fields = body.splitn(4, ':');
+        let invalid = || String::from("无效的回调数据");
+        let instance = fields.next().and_then(from_base36).ok_or_else(invalid)?;
+        let node = match fields.next().ok_or_else(invalid)? {
+            "" => None,
+            node => Some(
+                from_base36(node)
+                    .and_then(|node| i32::try_from(node).ok())
+                    .ok_or_else(invalid)?,
+            ),
+        };
+        let page = fields
+            .next()
+            .and_then(from_base36)
+            .and_then(|page| usize::try_from(page).ok())
+            .ok_or_else(invalid)?;
+        let arg = fields
+            .next()
+            .filter(|arg| !arg.is_empty())
+            .map(str::to_string);
+
+        Ok(Self {
+            action,
+            instance,
+            node,
+            page,
+            arg,
+        })
+    }
+}
+
+/// 节点状态卡片的回调数据, 分组过长时退化为全局翻页
+pub fn node_callback(instance: i64, node: i32, group: Option<&str>) -> String {
+    let data = CallbackData::new(CallbackAction::Node, instance).node(node);
+    match group.map(|group| data.clone().arg(group)) {
+        Some(grouped) if grouped.fits() => grouped.encode(),
+        _ => data.encode(),
+    }
+}
+
+fn encode_head(version: u8, action: CallbackAction) -> String {
+    format!("{}{}", to_base36(version.into()), action.code())
+}
+
+/// 版本可能多于一位, 动作总是最后一个字符
+fn decode_head(head: &str) -> Option<(i64, CallbackAction)> {
+    let mut chars = head.chars();
+    let action = chars.next_back().and_then(CallbackAction::from_code)?;
+    let version = chars.as_str();
+    if version.starts_with('-') {
+        return None;
+    }
+    Some((from_base36(version)?, action))
+}
+
+fn to_base36(number: i64) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut rest = number.unsigned_abs();
+    let mut digits = vec![];
+    loop {
+        digits.push(DIGITS[(rest % 36) as usize]);
+        rest /= 36;
+        if rest == 0 {
+            break;
+        }
+    }
+    if number < 0 {
+        digits.push(b'-');
+    }
+    digits.reverse();
+
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+fn from_base36(text: &str) -> Option<i64> {
+    i64::from_str_radix(text, 36).ok()
+}
+
+fn secret() -> Option<String> {
+    env::var("CALLBACK_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+fn mac(secret: Option<&str>, head: &str, body: &str) -> Option<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret?.as_bytes()).ok()?;
+    mac.update(head.as_bytes());
+    mac.update(b":");
+    mac.update(body.as_bytes());
+    Some(mac)
+}
+
+/// 未配置 `callback_secret` 时不签名
+fn sign(secret: Option<&str>, head: &str, body: &str) -> Option<String> {
+    let tag = mac(secret, head, body)?.finalize().into_bytes();
+    Some(
+        tag.iter()
+            .take(SIGNATURE_LEN)
+            .fold(String::new(), |mut signature, byte| {
+                let _ = write!(signature, "{byte:02x}");
+                signature
+            }),
+    )
+}
+
+fn verify(secret: Option<&str>, head: &str, body: &str, signature: &str) -> bool {
+    let Some(mac) = mac(secret, head, body) else {
+        return true;
+    };
+
+    let bytes: Option<Vec<u8>> = (0..signature.len())
+        .step_by(2)
+        .map(|i| {
+            signature
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect();
+
+    match bytes {
+        Some(bytes) if bytes.len() == SIGNATURE_LEN => mac.verify_truncated_left(&bytes).is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: Option<&str> = Some("test-secret");
+
+    fn worst_case() -> CallbackData {
+        CallbackData::new(CallbackAction::Node, i64::MIN)
+            .node(i32::MIN)
+            .page(usize::try_from(i64::MAX).unwrap())
+    }
+
+    #[test]
+    fn round_trips_all_actions() {
+        for action in [
+            CallbackAction::Node,
+            CallbackAction::Nodes,
+            CallbackAction::Find,
+            CallbackAction::Regions,
+            CallbackAction::Region,
+        ] {
+            let data = CallbackData::new(action, 123_456_789)
+                .node(42)
+                .page(3)
+                .arg("os=debian mem>2G");
+            for secret in [None, SECRET] {
+                let encoded = data.encode_with(secret);
+                assert_eq!(
+                    CallbackData::decode_with(&encoded, secret),
+                    Ok(data.clone())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn arg_may_contain_separator() {
+        let data = CallbackData::new(CallbackAction::Find, 1).arg("name=a:b");
+        let encoded = data.encode_with(SECRET);
+        assert_eq!(CallbackData::decode_with(&encoded, SECRET), Ok(data));
+    }
+
+    #[test]
+    fn worst_case_fields_fit_limit() {
+        let data = worst_case();
+        let encoded = data.encode_with(SECRET);
+        assert!(encoded.len() <= MAX_CALLBACK_LEN, "{encoded}");
+        assert_eq!(CallbackData::decode_with(&encoded, SECRET), Ok(data));
+
+        let room = MAX_CALLBACK_LEN - encoded.len();
+        let full = worst_case().arg(&"a".repeat(room));
+        assert_eq!(full.encode_with(SECRET).len(), MAX_CALLBACK_LEN);
+        let over = worst_case().arg(&"a".repeat(room + 1));
+        assert!(over.encode_with(SECRET).len() > MAX_CALLBACK_LEN);
+    }
+
+    #[test]
+    fn long_group_falls_back_to_global_paging() {
+        let group = "分组".repeat(20);
+        let encoded = node_callback(i64::MAX, i32::MAX, Some(&group));
+        assert!(encoded.len() <= MAX_CALLBACK_LEN);
+        assert_eq!(CallbackData::decode(&encoded).unwrap().arg, None);
+    }
+
+    #[test]
+    fn rejects_tampered_or_missing_signature() {
+        let encoded = CallbackData::new(CallbackAction::Node, 1)
+            .node(2)
+            .encode_with(SECRET);
+
+        let tampered = encoded.replacen(":1:2:", ":2:2:", 1);
+        assert_ne!(tampered, encoded);
+        assert_eq!(
+            CallbackData::decode_with(&tampered, SECRET),
+            Err(String::from("按钮签名无效"))
+        );
+
+        let unsigned = CallbackData::new(CallbackAction::Node, 1)
+            .node(2)
+            .encode_with(None);
+        assert_eq!(
+            CallbackData::decode_with(&unsigned, SECRET),
+            Err(String::from("按钮签名无效"))
+        );
+
+        assert!(CallbackData::decode_with(&encoded, Some("other-secret")).is_err());
+    }
+
+    #[test]
+    fn rejects_old_versions_and_garbage() {
+        let expired = Err(String::from("按钮已过期，请重新发送命令"));
+        let encoded = CallbackData::new(CallbackAction::Node, 1).encode_with(None);
+
+        let old = encoded.replacen(&CALLBACK_VERSION.to_string(), "0", 1);
+        assert_eq!(CallbackData::decode_with(&old, None), expired);
+        // 引入版本号之前的按钮
+        assert_eq!(CallbackData::decode_with("node:1:2", None), expired);
+        assert_eq!(CallbackData::decode_with("", None), expired);
+        assert_eq!(CallbackData::decode_with("1x::1:::", None), expired);
+    }
+
+    #[test]
+    fn versions_past_nine_round_trip() {
+        for version in [1, 9, 10, 35, 36, u8::MAX] {
+            for action in [CallbackAction::Node, CallbackAction::Region] {
+                let head = encode_head(version, action);
+                assert_eq!(decode_head(&head), Some((i64::from(version), action)));
+            }
+        }
+        assert_eq!(encode_head(10, CallbackAction::Node), "an");
+        assert_eq!(decode_head("n"), None);
+    }
+
+    #[test]
+    fn base36_round_trips() {
+        for number in [0, 1, 35, 36, -1, i64::MAX, i64::MIN] {
+            assert_eq!(from_base36(&to_base36(number)), Some(number));
+        }
+    }
+}
@@ -1,15 +1,14 @@
 use crate::ErrorString;
-use crate::connection::create_reqwest_client;
-use crate::db::{DB_POOL, query_monitor_by_telegram_id};
+use crate::connection::client::{KomariApi, KomariClient};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiNodes {
     pub status: String,
     pub data: Vec<ApiNodesData>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiNodesData {
     pub uuid: String,
     pub name: String,
@@ -25,6 +24,10 @@ pub struct ApiNodesData {
     pub swap_total: u64,
     pub disk_total: u64,
     pub price: Option<f64>,
+    /// 计费周期天数, 0 或负数为未设置或一次性付款
+    pub billing_cycle: Option<i64>,
+    /// 主控中填写的货币符号, 如 `$` 或 `¥`
+    pub currency: Option<String>,
     pub expired_at: Option<String>,
     pub group: Option<String>,
     pub tags: Option<String>,
@@ -32,58 +35,86 @@ pub struct ApiNodesData {
     pub updated_at: Option<String>,
 }
 
-pub async fn get_api_nodes(telegram_id: i64) -> Result<ApiNodes, ErrorString> {
-    let client = create_reqwest_client().await?;
-
-    let monitor = query_monitor_by_telegram_id(
-        DB_POOL
-            .get()
-            .unwrap_or_else(|| panic!("数据库连接池未初始化")),
-        telegram_id,
-    )
-    .await?;
-
-    let url = if let Some(monitor) = monitor {
-        format!("{}/api/nodes", monitor.monitor_http_url)
-    } else {
-        return Err(ErrorString::from(
-            "服务器未连接，请先使用 /connect [http url] 连接".to_string(),
-        ));
-    };
+impl ApiNodesData {
+    /// Komari 以分号分隔多个标签
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .as_deref()
+            .unwrap_or_default()
+            .split([';', ','])
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(std::string::ToString::to_string)
+            .collect()
+    }
 
-    let res = client
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| ErrorString::from(e.to_string()))?;
+    /// 主控中设置的计费周期, 按月取整
+    pub fn billing_cycle_months(&self) -> Option<i64> {
+        let days = self.billing_cycle.filter(|days| *days > 0)?;
+        Some(((days * 12 + 182) / 365).max(1))
+    }
 
-    if res.status().as_u16() == 401 {
-        return Err(ErrorString::from(String::from("主控开启了私有模式")));
+    /// 主控中设置的货币, 常见符号转为货币代码
+    pub fn currency_code(&self) -> Option<String> {
+        let currency = self.currency.as_deref().map(str::trim)?;
+        let code = match currency {
+            "" => return None,
+            "$" | "US$" => "USD",
+            "¥" | "￥" | "RMB" | "rmb" => "CNY",
+            "€" => "EUR",
+            "£" => "GBP",
+            "HK$" => "HKD",
+            "NT$" => "TWD",
+            "₩" => "KRW",
+            "₽" => "RUB",
+            "₹" => "INR",
+            currency => return Some(currency.to_uppercase()),
+        };
+        Some(code.to_string())
     }
 
-    if !res.status().is_success() {
-        return Err(ErrorString::from(format!(
-            "服务器返回错误：{}",
-            res.status()
-        )));
+    pub fn group_name(&self) -> String {
+        match self.group.as_deref().map(str::trim) {
+            Some(group) if !group.is_empty() => group.to_string(),
+            _ => String::from("未分组"),
+        }
     }
+}
 
-    let text = res
-        .text()
-        .await
-        .map_err(|e| ErrorString::from(format!("Text 解析错误: {e}")))?
-        .trim()
-        .to_string();
+pub async fn get_api_nodes(telegram_id: i64) -> Result<ApiNodes, ErrorString> {
+    KomariClient::for_user(telegram_id).await?.nodes().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes() -> ApiNodes {
+        serde_json::from_str(include_str!("../../fixtures/komari/nodes.json")).unwrap()
+    }
 
-    let json = serde_json::from_str::<ApiNodes>(&text)
-        .map_err(|e| ErrorString::from(format!("JSON 解析错误: {e}")))?;
+    #[test]
+    fn billing_cycle_days_round_to_months() {
+        let mut node = nodes().data.remove(0);
+        for (days, months) in [(30, 1), (92, 3), (184, 6), (365, 12), (720, 24), (1095, 36)] {
+            node.billing_cycle = Some(days);
+            assert_eq!(node.billing_cycle_months(), Some(months), "{days} 天");
+        }
 
-    if json.status != "success" {
-        return Err(ErrorString::from(format!(
-            "服务器返回错误：{}",
-            json.status
-        )));
+        node.billing_cycle = Some(-1);
+        assert_eq!(node.billing_cycle_months(), None);
     }
 
-    Ok(json)
+    #[test]
+    fn currency_symbols_map_to_codes() {
+        let nodes = nodes();
+        assert_eq!(nodes.data[0].currency_code().as_deref(), Some("USD"));
+        assert_eq!(nodes.data[0].billing_cycle_months(), Some(1));
+
+        let mut node = nodes.data[1].clone();
+        node.currency = Some(String::from("eur"));
+        assert_eq!(node.currency_code().as_deref(), Some("EUR"));
+        node.currency = Some(String::from(" "));
+        assert_eq!(node.currency_code(), None);
+    }
 }
@@ -0,0 +1,124 @@
+use crate::ErrorString;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::schedule::parse_duration;
+use serde::{Deserialize, Serialize};
+
+/// 可查询的最长时间范围, 与 Komari 默认的记录保留时长一致
+pub const MAX_HOURS: u32 = 720;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiRecords {
+    pub status: String,
+    pub data: ApiRecordsData,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiRecordsData {
+    #[serde(default)]
+    pub records: Vec<ApiRecord>,
+}
+
+/// Komari 每分钟汇总的一条负载记录, 网络速率单位为 B/s
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ApiRecord {
+    pub time: String,
+    pub cpu: f64,
+    pub ram: u64,
+    pub ram_total: u64,
+    pub disk: u64,
+    pub disk_total: u64,
+    pub load: f64,
+    pub net_in: u64,
+    pub net_out: u64,
+    pub process: u64,
+    pub connections: u64,
+    pub connections_udp: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiPingRecords {
+    pub status: String,
+    pub data: ApiPingRecordsData,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct ApiPingRecordsData {
+    pub records: Vec<ApiPingRecord>,
+    pub tasks: Vec<ApiPingTask>,
+}
+
+/// 一次延迟探测结果, 超时或丢包时 `value` 为负数
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ApiPingRecord {
+    pub task_id: i64,
+    pub time: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ApiPingTask {
+    pub id: i64,
+    pub name: String,
+    pub interval: i64,
+    pub loss: f64,
+}
+
+/// 解析 `6h`, `3d`, `1w` 形式的时间范围, 不足一小时按一小时计
+pub fn parse_hours(text: &str) -> Result<u32, ErrorString> {
+    let duration = parse_duration(text).ok_or(format!("无效的时间范围: {text}"))?;
+    let hours = u32::try_from((duration.num_minutes() + 59) / 60).unwrap_or(u32::MAX);
+
+    if hours > MAX_HOURS {
+        return Err(format!("时间范围过长，最多 {} 天", MAX_HOURS / 24));
+    }
+
+    Ok(hours.max(1))
+}
+
+/// `/api/records/load`, 最近 `hours` 小时的负载记录
+pub async fn get_api_records(
+    telegram_id: i64,
+    uuid: &str,
+    hours: u32,
+) -> Result<ApiRecords, ErrorString> {
+    KomariClient::for_user(telegram_id)
+        .await?
+        .records(uuid, hours)
+        .await
+}
+
+/// `/api/records/ping`, 最近 `hours` 小时的延迟探测记录
+pub async fn get_api_ping_records(
+    telegram_id: i64,
+    uuid: &str,
+    hours: u32,
+) -> Result<ApiPingRecords, ErrorString> {
+    KomariClient::for_user(telegram_id)
+        .await?
+        .ping_records(uuid, hours)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hours_rounds_up_and_caps_range() {
+        assert_eq!(parse_hours("30m"), Ok(1));
+        assert_eq!(parse_hours("1d"), Ok(24));
+        assert_eq!(parse_hours("30d"), Ok(MAX_HOURS));
+        assert!(parse_hours("31d").is_err());
+    }
+
+    #[test]
+    fn parse_hours_rejects_huge_input() {
+        assert!(parse_hours("9999999999999w").is_err());
+        assert!(parse_hours("999999999999999d").is_err());
+        assert!(parse_hours("99999999999999999999h").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::ApiNodes;
+use crate::connection::api_public::ApiPublic;
+use crate::connection::api_records::{ApiPingRecords, ApiRecords, MAX_HOURS};
+use crate::connection::api_version::ApiVersion;
+use crate::connection::create_reqwest_client;
+use crate::connection::ws_get::{ApiWs, connect_ws};
+use crate::db::{DB_POOL, Monitor, query_monitor_by_telegram_id};
+use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+
+/// 历史记录在该时间内直接使用缓存的响应
+const RECORDS_CACHE_TTL: Duration = Duration::from_mins(1);
+
+/// 以完整请求地址为键缓存历史记录的响应正文, 重启后失效
+static RECORDS_CACHE: LazyLock<Mutex<HashMap<String, (Instant, String)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 节点与站点信息的默认缓存时间, 可在 config.json 中以 `metadata_cache_secs` 修改, 0 为不缓存
+const DEFAULT_METADATA_TTL: Duration = Duration::from_mins(5);
+
+/// 缓存的节点与站点信息接口
+const METADATA_PATHS: [&str; 2] = ["/api/nodes", "/api/public"];
+
+/// 一个请求地址的缓存, 请求期间持有锁, 同时到达的请求等待并共用结果
+type MetadataSlot = Arc<tokio::sync::Mutex<Option<(Instant, String)>>>;
+
+/// 以完整请求地址为键缓存节点与站点信息的响应正文, 重启后失效
+static METADATA_CACHE: LazyLock<Mutex<HashMap<String, MetadataSlot>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 请求主控的默认超时时间, 可在 config.json 中以 `request_timeout_secs` 修改
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn metadata_ttl() -> Duration {
+    env::var("METADATA_CACHE_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .map_or(DEFAULT_METADATA_TTL, Duration::from_secs)
+}
+
+/// HTTP 请求与 Websocket 读取一帧数据的超时时间
+fn request_timeout() -> Duration {
+    env::var("REQUEST_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .filter(|secs| *secs > 0)
+        .map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_secs)
+}
+
+/// 一个 Komari 主控提供的数据, 命令只依赖该 trait, 可替换为内存中的实现
+pub trait KomariApi: Sync {
+    /// 所连接主控的数据库记录
+    fn monitor(&self) -> &Monitor;
+
+    /// `/api/public`
+    fn public(&self) -> impl Future<Output = Result<ApiPublic, ErrorString>> + Send;
+
+    /// `/api/nodes`
+    fn nodes(&self) -> impl Future<Output = Result<ApiNodes, ErrorString>> + Send;
+
+    /// `/api/version`
+    fn version(&self) -> impl Future<Output = Result<ApiVersion, ErrorString>> + Send;
+
+    /// Websocket `/api/clients` 的一帧实时数据
+    fn snapshot(&self) -> impl Future<Output = Result<ApiWs, ErrorString>> + Send;
+
+    /// `/api/records/load`, 最近 `hours` 小时的负载记录
+    fn records(
+        &self,
+        uuid: &str,
+        hours: u32,
+    ) -> impl Future<Output = Result<ApiRecords, ErrorString>> + Send;
+
+    /// `/api/records/ping`, 最近 `hours` 小时的延迟探测记录
+    fn ping_records(
+        &self,
+        uuid: &str,
+        hours: u32,
+    ) -> impl Future<Output = Result<ApiPingRecords, ErrorString>> + Send;
+}
+
+/// 各接口响应外层的 `status` 字段
+trait Envelope {
+    fn status(&self) -> &str;
+}
+
+impl Envelope for ApiPublic {
+    fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+impl Envelope for ApiNodes {
+    fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+impl Envelope for ApiVersion {
+    fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+impl Envelope for ApiRecords {
+    fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+impl Envelope for ApiPingRecords {
+    fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+/// 通过 HTTP 与 Websocket 访问真实主控
+#[derive(Debug, Clone)]
+pub struct KomariClient {
+    monitor: Monitor,
+}
+
+impl KomariClient {
+    pub fn new(monitor: Monitor) -> Self {
+        Self { monitor }
+    }
+
+    /// 该用户已保存连接的主控
+    pub async fn for_user(telegram_id: i64) -> Result<Self, ErrorString> {
+        let db_pool = DB_POOL
+            .get()
+            .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+        query_monitor_by_telegram_id(db_pool, telegram_id)
+            .await?
+            .map(Self::new)
+            .ok_or(String::from(
+                "服务器未连接，请先使用 /connect [http url] 连接",
+            ))
+    }
+
+    /// 请求主控的某个路径, 返回去除首尾空白的响应正文
+    async fn fetch(&self, path: &str) -> Result<String, ErrorString> {
+        let client = create_reqwest_client().await?;
+
+        let res = client
+            .get(format!("{}{path}", self.monitor.monitor_http_url))
+            .timeout(request_timeout())
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    String::from("请求主控超时")
+                } else {
+                    e.to_string()
+                }
+            })?;
+
+        if res.status().as_u16() == 401 {
+            return Err(ErrorString::from(String::from("主控开启了私有模式")));
+        }
+
+        if !res.status().is_success() {
+            return Err(ErrorString::from(format!(
+                "服务器返回错误：{}",
+                res.status()
+            )));
+        }
+
+        Ok(res
+            .text()
+            .await
+            .map_err(|e| ErrorString::from(format!("Text 解析错误: {e}")))?
+            .trim()
+            .to_string())
+    }
+
+    fn parse<T: DeserializeOwned + Envelope>(text: &str) -> Result<T, ErrorString> {
+        let json = serde_json::from_str::<T>(text)
+            .map_err(|e| ErrorString::from(format!("JSON 解析错误: {e}")))?;
+
+        if json.status() != "success" {
+            return Err(ErrorString::from(format!(
+                "服务器返回错误：{}",
+                json.status()
+            )));
+        }
+
+        Ok(json)
+    }
+
+    async fn get<T: DeserializeOwned + Envelope>(&self, path: &str) -> Result<T, ErrorString> {
+        Self::parse(&self.fetch(path).await?)
+    }
+
+    /// 请求节点或站点信息, 缓存有效期内直接使用上次成功的响应
+    async fn get_metadata<T: DeserializeOwned + Envelope>(
+        &self,
+        path: &str,
+    ) -> Result<T, ErrorString> {
+        let ttl = metadata_ttl();
+        let key = format!("{}{path}", self.monitor.monitor_http_url);
+
+        let slot = METADATA_CACHE
+            .lock()
+            .ok()
+            .filter(|_| !ttl.is_zero())
+            .map(|mut cache| cache.entry(key).or_default().clone());
+        let Some(slot) = slot else {
+            return self.get(path).await;
+        };
+
+        let mut entry = slot.lock().await;
+        if let Some((_, text)) = entry
+            .as_ref()
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < ttl)
+        {
+            return Self::parse(text);
+        }
+
+        let text = self.fetch(path).await?;
+        let json = Self::parse(&text)?;
+        *entry = Some((Instant::now(), text));
+
+        Ok(json)
+    }
+
+    /// 清除该主控的节点与站点信息缓存, 在 `/connect` 与 `/update` 时调用
+    pub fn invalidate(&self) {
+        if let Ok(mut cache) = METADATA_CACHE.lock() {
+            for path in METADATA_PATHS {
+                cache.remove(&format!("{}{path}", self.monitor.monitor_http_url));
+            }
+        }
+    }
+
+    /// 请求 `/api/records/{kind}`, 相同的请求在缓存有效期内只发送一次
+    async fn get_records<T: DeserializeOwned + Envelope>(
+        &self,
+        kind: &str,
+        uuid: &str,
+        hours: u32,
+    ) -> Result<T, ErrorString> {
+        if !(1..=MAX_HOURS).contains(&hours) {
+            return Err(format!("时间范围应在 1 到 {MAX_HOURS} 小时之间"));
+        }
+
+        let path = format!(
+            "/api/records/{kind}?uuid={}&hours={hours}",
+            urlencoding::encode(uuid)
+        );
+        let key = format!("{}{path}", self.monitor.monitor_http_url);
+
+        let cached = RECORDS_CACHE.lock().ok().and_then(|cache| {
+            cache
+                .get(&key)
+                .filter(|(fetched_at, _)| fetched_at.elapsed() < RECORDS_CACHE_TTL)
+                .map(|(_, text)| text.clone())
+        });
+
+        let text = if let Some(text) = cached {
+            text
+        } else {
+            let text = self.fetch(&path).await?;
+            if let Ok(mut cache) = RECORDS_CACHE.lock() {
+                cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < RECORDS_CACHE_TTL);
+                cache.insert(key, (Instant::now(), text.clone()));
+            }
+            text
+        };
+
+        Self::parse(&text)
+    }
+}
+
+impl KomariApi for KomariClient {
+    fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    async fn public(&self) -> Result<ApiPublic, ErrorString> {
+        self.get_metadata("/api/public").await
+    }
+
+    async fn nodes(&self) -> Result<ApiNodes, ErrorString> {
+        self.get_metadata("/api/nodes").await
+    }
+
+    async fn version(&self) -> Result<ApiVersion, ErrorString> {
+        self.get("/api/version").await
+    }
+
+    async fn snapshot(&self) -> Result<ApiWs, ErrorString> {
+        let ws_connection =
+            connect_ws(&self.monitor.monitor_http_url, &self.monitor.monitor_ws_url).await?;
+
+        let (mut write, mut read) = ws_connection.split();
+
+        write
+            .send(Message::Text(Utf8Bytes::from("get")))
+            .await
+            .map_err(|_| String::from("无法发送数据"))?;
+
+        let Some(Ok(msg)) = tokio::time::timeout(request_timeout(), read.next())
+            .await
+            .map_err(|_| String::from("请求主控超时"))?
+        else {
+            return Err(String::from("数据接收出现错误"));
+        };
+
+        let data_str = msg
+            .to_text()
+            .map_err(|_| String::from("无法将 Websocket 返回内容转化为文本"))?;
+
+        serde_json::from_str(data_str)
+            .map_err(|e| format!("无法将 Websocket 响应内容转化为 JSON: {e}"))
+    }
+
+    async fn records(&self, uuid: &str, hours: u32) -> Result<ApiRecords, ErrorString> {
+        self.get_records("load", uuid, hours).await
+    }
+
+    async fn ping_records(&self, uuid: &str, hours: u32) -> Result<ApiPingRecords, ErrorString> {
+        self.get_records("ping", uuid, hours).await
+    }
+}
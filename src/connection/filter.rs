@@ -0,0 +1,76 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::ApiNodesData;
+
+/// 按 Komari 分组与标签筛选节点, 例如 `group=prod tag=cn2`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeFilter {
+    pub group: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl NodeFilter {
+    pub fn parse<S: AsRef<str>>(args: &[S]) -> Result<Self, ErrorString> {
+        let mut filter = Self::default();
+
+        for arg in args {
+            let (key, value) = arg
+                .as_ref()
+                .split_once('=')
+                .ok_or(format!("无法解析筛选条件: {}", arg.as_ref()))?;
+
+            match key {
+                "group" => filter.group = Some(value.to_string()),
+                "tag" => filter.tag = Some(value.to_string()),
+                _ => return Err(format!("未知的筛选条件: {key}，仅支持 group 与 tag")),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// 从 [`Self::describe`] 还原筛选条件, 用于翻页按钮
+    pub fn from_text(text: &str) -> Result<Self, ErrorString> {
+        Self::parse(&text.split_whitespace().collect::<Vec<_>>())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.group.is_none() && self.tag.is_none()
+    }
+
+    pub fn matches(&self, node: &ApiNodesData) -> bool {
+        let group_matches = self
+            .group
+            .as_ref()
+            .is_none_or(|group| node.group_name().eq_ignore_ascii_case(group));
+        let tag_matches = self.tag.as_ref().is_none_or(|tag| {
+            node.tag_list()
+                .iter()
+                .any(|node_tag| node_tag.eq_ignore_ascii_case(tag))
+        });
+
+        group_matches && tag_matches
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        if let Some(group) = &self.group {
+            parts.push(format!("group={group}"));
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("tag={tag}"));
+        }
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_round_trips() {
+        let filter = NodeFilter::parse(&["group=Asia", "tag=web"]).unwrap();
+        assert_eq!(NodeFilter::from_text(&filter.describe()).unwrap(), filter);
+        assert_eq!(NodeFilter::from_text("").unwrap(), NodeFilter::default());
+    }
+}
@@ -1,10 +1,17 @@
 pub mod api_nodes;
 pub mod api_public;
+pub mod api_records;
 pub mod api_version;
+pub mod client;
+pub mod filter;
+pub mod query;
 pub mod ws_get;
 
 use crate::ErrorString;
-use crate::db::{DB_POOL, Monitor, delete_monitor, insert_monitor, query_monitor_by_telegram_id};
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::db::{DB_POOL, Monitor, delete_monitor, insert_monitor};
+use crate::markup::Markup;
+use crate::units::load_units;
 use reqwest::Client;
 use teloxide::types::Message;
 use tokio::sync::OnceCell;
@@ -34,36 +41,22 @@ pub async fn first_init_read(msg: Message) -> Result<String, ErrorString> {
         return Err(String::from("无法获取用户ID"));
     };
 
-    let (public, nodes, version) = tokio::try_join!(
-        api_public::get_api_public(telegram_id),
-        api_nodes::get_api_nodes(telegram_id),
-        api_version::get_api_version(telegram_id)
-    )?;
+    let client = KomariClient::for_user(telegram_id).await?;
+    client.invalidate();
+    let (public, nodes, version) =
+        tokio::try_join!(client.public(), client.nodes(), client.version())?;
 
     let site_name = public.data.sitename;
     let site_description = public.data.description;
     let version = format!("{}-{}", version.data.version, version.data.hash);
     let nodes_count = nodes.data.len();
     let cores_count = nodes.data.iter().map(|node| node.cpu_cores).sum::<i32>();
-    let memory_total = nodes
-        .data
-        .iter()
-        .map(|node| node.mem_total as f64 / 1024.0 / 1024.0 / 1024.0)
-        .sum::<f64>();
-    let swap_total = nodes
-        .data
-        .iter()
-        .map(|node| node.swap_total as f64 / 1024.0 / 1024.0 / 1024.0)
-        .sum::<f64>();
-    let disk_total = nodes
-        .data
-        .iter()
-        .map(|node| node.disk_total as f64 / 1024.0 / 1024.0 / 1024.0)
-        .sum::<f64>();
+    let memory_total = nodes.data.iter().map(|node| node.mem_total).sum::<u64>();
+    let swap_total = nodes.data.iter().map(|node| node.swap_total).sum::<u64>();
+    let disk_total = nodes.data.iter().map(|node| node.disk_total).sum::<u64>();
+    let units = load_units(telegram_id).await?;
 
-    let Some(monitor_bak) = query_monitor_by_telegram_id(db_pool, telegram_id).await? else {
-        return Ok("未找到该用户".to_string());
-    };
+    let monitor_bak = client.monitor().clone();
 
     delete_monitor(db_pool, msg.clone()).await?;
 
@@ -85,35 +78,56 @@ pub async fn first_init_read(msg: Message) -> Result<String, ErrorString> {
         return Err(format!("无法更新数据库: {e}"));
     }
 
-    Ok(format!(
-        "成功读取 Komari 服务信息！\n\
-         站点名称：`{site_name}`\n\
-         站点详情：`{site_description}`\n\
-         Komari 版本：`{version}`\n\
-         节点数量：`{nodes_count}`\n\
-         CPU 核心总数：`{cores_count}`\n\
-         内存总量：`{memory_total:.2} GiB`\n\
-         交换分区总量：`{swap_total:.2} GiB`\n\
-         硬盘总量：`{disk_total:.2} GiB`"
-    ))
+    let mut message = Markup::default();
+    message
+        .text("成功读取 Komari 服务信息！")
+        .line()
+        .field("站点名称", &site_name)
+        .field("站点详情", &site_description)
+        .field("Komari 版本", &version)
+        .field("节点数量", nodes_count.to_string())
+        .field("CPU 核心总数", cores_count.to_string())
+        .field("内存总量", units.bytes(memory_total))
+        .field("交换分区总量", units.bytes(swap_total))
+        .text("硬盘总量: ")
+        .code(units.bytes(disk_total));
+
+    Ok(message.build())
 }
 
-pub fn msg_fixer(msg: String) -> String {
-    msg.replace('.', r"\.")
-        .replace('-', r"\-")
-        .replace('|', r"\|")
-        .replace('(', r"\(")
-        .replace(')', r"\)")
-        .replace('#', r"\#")
-        .replace('+', r"\+")
-        .replace('=', r"\=")
-        .replace('{', r"\{")
-        .replace('}', r"\}")
-        .replace('[', r"\[")
-        .replace(']', r"\]")
-        .replace('_', r"\_")
-        .replace('>', r"\>")
-        .replace('<', r"\<")
-        .replace('&', r"\&")
-        .replace('!', r"\!")
+/// 按 Bot 序号或节点名称查找节点, 返回 (uuid, 名称)
+pub async fn resolve_node(telegram_id: i64, node: &str) -> Result<(String, String), ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let node = lookup_node(&ws_data, &nodes, node)?;
+    Ok((node.uuid.clone(), node.name.clone()))
+}
+
+/// 在已获取的数据中按 Bot 序号、名称或 uuid 查找节点
+pub fn lookup_node<'a>(
+    ws_data: &ws_get::ApiWs,
+    nodes: &'a api_nodes::ApiNodes,
+    node: &str,
+) -> Result<&'a api_nodes::ApiNodesData, ErrorString> {
+    if let Ok(index) = node.parse::<i32>() {
+        let uuid =
+            ws_get::status::uuid_by_index(ws_data, nodes, index).ok_or("找不到该序号的服务器")?;
+
+        if let Some(node) = nodes.data.iter().find(|node| node.uuid == uuid) {
+            return Ok(node);
+        }
+    }
+
+    nodes
+        .data
+        .iter()
+        .find(|data| data.name == node || data.uuid == node)
+        .or_else(|| {
+            nodes
+                .data
+                .iter()
+                .find(|data| data.name.to_lowercase() == node.to_lowercase())
+        })
+        .ok_or(format!("找不到服务器: {node}"))
 }
@@ -0,0 +1,303 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::ApiNodesData;
+use crate::units::{parse_size, size_power};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Os,
+    Arch,
+    Virt,
+    Kernel,
+    Cpu,
+    Gpu,
+    Region,
+    Group,
+    Tag,
+    Mem,
+    Swap,
+    Disk,
+    Cores,
+    Price,
+}
+
+impl Field {
+    fn parse(field: &str) -> Option<Self> {
+        match field {
+            "name" => Some(Self::Name),
+            "os" => Some(Self::Os),
+            "arch" => Some(Self::Arch),
+            "virt" | "virtualization" => Some(Self::Virt),
+            "kernel" | "kern" => Some(Self::Kernel),
+            "cpu" => Some(Self::Cpu),
+            "gpu" => Some(Self::Gpu),
+            "region" => Some(Self::Region),
+            "group" => Some(Self::Group),
+            "tag" => Some(Self::Tag),
+            "mem" | "ram" => Some(Self::Mem),
+            "swap" => Some(Self::Swap),
+            "disk" => Some(Self::Disk),
+            "cores" => Some(Self::Cores),
+            "price" => Some(Self::Price),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::Mem | Self::Swap | Self::Disk | Self::Cores | Self::Price
+        )
+    }
+
+    fn text(self, node: &ApiNodesData) -> Vec<String> {
+        match self {
+            Self::Name => vec![node.name.clone()],
+            Self::Os => vec![node.os.clone()],
+            Self::Arch => vec![node.arch.clone()],
+            Self::Virt => vec![node.virtualization.clone()],
+            Self::Kernel => vec![node.kernel_version.clone()],
+            Self::Cpu => vec![node.cpu_name.clone()],
+            Self::Gpu => vec![node.gpu_name.clone()],
+            Self::Region => vec![node.region.clone()],
+            Self::Group => vec![node.group_name()],
+            Self::Tag => node.tag_list(),
+            Self::Mem | Self::Swap | Self::Disk | Self::Cores | Self::Price => vec![],
+        }
+    }
+
+    fn number(self, node: &ApiNodesData) -> Option<f64> {
+        match self {
+            Self::Mem => Some(node.mem_total as f64),
+            Self::Swap => Some(node.swap_total as f64),
+            Self::Disk => Some(node.disk_total as f64),
+            Self::Cores => Some(f64::from(node.cpu_cores)),
+            Self::Price => node.price,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    /// 包含, 不区分大小写
+    Contains,
+    /// 不包含
+    NotContains,
+    /// 前缀匹配, 例如 `kernel~5.10`
+    Prefix,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+impl Operator {
+    /// 按长度优先匹配, 避免 `>=` 被识别为 `>`
+    const ALL: [(&'static str, Self); 7] = [
+        ("!=", Self::NotContains),
+        (">=", Self::GreaterEqual),
+        ("<=", Self::LessEqual),
+        ("=", Self::Contains),
+        ("~", Self::Prefix),
+        (">", Self::Greater),
+        ("<", Self::Less),
+    ];
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::Greater | Self::GreaterEqual | Self::Less | Self::LessEqual
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: Field,
+    operator: Operator,
+    value: String,
+    number: Option<f64>,
+    /// 数值 `=` 与 `!=` 的容差, 为输入精度的一半, 例如 `mem=2G` 匹配 1.5G 到 2.5G
+    tolerance: f64,
+}
+
+/// 输入数值最后一位所代表的大小, 容量按其单位计算, 例如 `2G` 为 1G, `1.5` 为 0.1
+fn precision(field: Field, value: &str) -> f64 {
+    let split = value
+        .find(|char: char| !char.is_ascii_digit() && char != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let decimals = number
+        .split_once('.')
+        .map_or(0, |(_, decimals)| decimals.len());
+    let scale = 10f64.powi(i32::try_from(decimals).unwrap_or(i32::MAX));
+
+    let unit_size = match field {
+        Field::Mem | Field::Swap | Field::Disk => 1024f64.powi(size_power(unit).unwrap_or(0)),
+        _ => 1.0,
+    };
+
+    unit_size / scale
+}
+
+impl Condition {
+    fn parse(term: &str) -> Result<Self, ErrorString> {
+        let (position, symbol, operator) = Operator::ALL
+            .iter()
+            .filter_map(|(symbol, operator)| {
+                term.find(symbol)
+                    .map(|position| (position, *symbol, *operator))
+            })
+            .min_by_key(|(position, symbol, _)| (*position, usize::MAX - symbol.len()))
+            .ok_or(format!("无法解析查询条件: {term}"))?;
+
+        let field_name = term[..position].trim().to_lowercase();
+        let value = term[position + symbol.len()..].trim().to_string();
+
+        let field = Field::parse(&field_name).ok_or(format!("未知的字段: {field_name}"))?;
+        if value.is_empty() {
+            return Err(format!("查询条件缺少值: {term}"));
+        }
+
+        let number = if field.is_numeric() {
+            let number = match field {
+                Field::Mem | Field::Swap | Field::Disk => {
+                    parse_size(&value).map(|size| size as f64)
+                }
+                _ => value.parse::<f64>().ok(),
+            };
+            Some(number.ok_or(format!("无法解析数值: {value}"))?)
+        } else {
+            None
+        };
+
+        if field.is_numeric() && operator == Operator::Prefix {
+            return Err(format!("{field_name} 仅支持 = != > >= < <="));
+        }
+        if !field.is_numeric() && operator.is_numeric() {
+            return Err(format!("{field_name} 不支持数值比较"));
+        }
+
+        Ok(Self {
+            tolerance: precision(field, &value) / 2.0,
+            field,
+            operator,
+            value,
+            number,
+        })
+    }
+
+    fn within(&self, actual: f64, expected: f64) -> bool {
+        actual >= expected - self.tolerance && actual < expected + self.tolerance
+    }
+
+    fn matches(&self, node: &ApiNodesData) -> bool {
+        if let Some(expected) = self.number {
+            let Some(actual) = self.field.number(node) else {
+                return false;
+            };
+
+            return match self.operator {
+                Operator::Greater => actual > expected,
+                Operator::GreaterEqual => actual >= expected,
+                Operator::Less => actual < expected,
+                Operator::LessEqual => actual <= expected,
+                Operator::Contains => self.within(actual, expected),
+                Operator::NotContains => !self.within(actual, expected),
+                Operator::Prefix => false,
+            };
+        }
+
+        let value = self.value.to_lowercase();
+        let texts = self.field.text(node);
+        let any = |predicate: &dyn Fn(&str) -> bool| {
+            texts.iter().any(|text| predicate(&text.to_lowercase()))
+        };
+
+        match self.operator {
+            Operator::Contains => any(&|text| text.contains(&value)),
+            Operator::NotContains => !any(&|text| text.contains(&value)),
+            Operator::Prefix => any(&|text| text.starts_with(&value)),
+            _ => false,
+        }
+    }
+}
+
+/// `/find` 查询, 多个条件之间为且关系, 例如 `os=debian arch=arm64 kernel~5.10 mem>2G`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeQuery {
+    text: String,
+    conditions: Vec<Condition>,
+}
+
+impl NodeQuery {
+    pub fn parse<S: AsRef<str>>(args: &[S]) -> Result<Self, ErrorString> {
+        if args.is_empty() {
+            return Err(String::from("查询条件不能为空"));
+        }
+
+        Ok(Self {
+            text: args.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(" "),
+            conditions: args
+                .iter()
+                .map(|term| Condition::parse(term.as_ref()))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// 从 [`Self::text`] 还原查询, 用于翻页按钮
+    pub fn from_text(text: &str) -> Result<Self, ErrorString> {
+        Self::parse(&text.split_whitespace().collect::<Vec<_>>())
+    }
+
+    pub fn matches(&self, node: &ApiNodesData) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(node))
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::api_nodes::ApiNodes;
+
+    fn matching(query: &str) -> Vec<String> {
+        let nodes: ApiNodes =
+            serde_json::from_str(include_str!("../../fixtures/komari/nodes.json")).unwrap();
+        let query = NodeQuery::from_text(query).unwrap();
+        nodes
+            .data
+            .into_iter()
+            .filter(|node| query.matches(node))
+            .map(|node| node.name)
+            .collect()
+    }
+
+    #[test]
+    fn size_equality_uses_input_precision() {
+        // HK-Web_01 的内存约为 1.92 GiB
+        assert_eq!(matching("mem=2G"), ["HK-Web_01"]);
+        assert!(matching("mem=2.0G").is_empty());
+        assert_eq!(matching("mem=1.9G"), ["HK-Web_01"]);
+        assert_eq!(matching("mem!=2G"), ["JP Tokyo"]);
+    }
+
+    #[test]
+    fn number_equality_uses_input_precision() {
+        assert_eq!(matching("price=5.5"), ["HK-Web_01"]);
+        assert_eq!(matching("cores=4"), ["JP Tokyo"]);
+        assert_eq!(matching("cores!=4"), ["HK-Web_01"]);
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let query = NodeQuery::parse(&["os=debian", "mem>1G"]).unwrap();
+        assert_eq!(NodeQuery::from_text(query.text()).unwrap(), query);
+    }
+}
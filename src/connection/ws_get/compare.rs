@@ -0,0 +1,232 @@
+use crate::ErrorString;
+use crate::Reply;
+use crate::connection::api_nodes::ApiNodesData;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::lookup_node;
+use crate::connection::ws_get::ApiWsDataHashMapValue;
+use crate::markup::Markup;
+use crate::units::{Units, load_units};
+use std::fmt::Write;
+
+const MAX_CELL_WIDTH: usize = 24;
+
+/// 数值差异超过该比例时视为不同
+const NUMERIC_TOLERANCE: f64 = 0.1;
+
+struct Row {
+    label: &'static str,
+    left: String,
+    right: String,
+    differs: bool,
+}
+
+impl Row {
+    fn text(label: &'static str, left: String, right: String) -> Self {
+        let differs = left != right;
+        Self {
+            label,
+            left,
+            right,
+            differs,
+        }
+    }
+
+    fn numeric(
+        label: &'static str,
+        values: [Option<f64>; 2],
+        format: impl Fn(f64) -> String,
+    ) -> Self {
+        let differs = match values {
+            [Some(left), Some(right)] => {
+                (left - right).abs() > left.abs().max(right.abs()) * NUMERIC_TOLERANCE
+            }
+            [None, None] => false,
+            _ => true,
+        };
+        let [left, right] = values.map(|value| value.map_or_else(|| String::from("-"), &format));
+        Self {
+            label,
+            left,
+            right,
+            differs,
+        }
+    }
+}
+
+/// 等宽字体下中日韩字符占两列
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|char| match char as u32 {
+            0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFF00..=0xFF60 => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+fn truncate(text: &str) -> String {
+    if display_width(text) <= MAX_CELL_WIDTH {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    for char in text.chars() {
+        if display_width(&truncated) + display_width(&char.to_string()) > MAX_CELL_WIDTH - 1 {
+            break;
+        }
+        truncated.push(char);
+    }
+    truncated.push('…');
+    truncated
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!(
+        "{text}{}",
+        " ".repeat(width.saturating_sub(display_width(text)))
+    )
+}
+
+fn percent(used: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| used as f64 / total as f64 * 100.0)
+}
+
+fn build_rows(
+    nodes: [(&ApiNodesData, Option<&ApiWsDataHashMapValue>); 2],
+    units: &Units,
+) -> Vec<Row> {
+    let [(left, left_ws), (right, right_ws)] = nodes;
+    let text =
+        |label, field: fn(&ApiNodesData) -> String| Row::text(label, field(left), field(right));
+    let live = |field: fn(&ApiWsDataHashMapValue) -> Option<f64>| {
+        [left_ws.and_then(field), right_ws.and_then(field)]
+    };
+
+    vec![
+        text("REGION", |node| node.region.clone()),
+        text("CPU", |node| node.cpu_name.clone()),
+        text("CORES", |node| node.cpu_cores.to_string()),
+        text("ARCH", |node| node.arch.clone()),
+        text("VIRT", |node| node.virtualization.clone()),
+        text("OS", |node| node.os.clone()),
+        text("KERN", |node| node.kernel_version.clone()),
+        Row::text(
+            "MEM",
+            units.bytes(left.mem_total),
+            units.bytes(right.mem_total),
+        ),
+        Row::text(
+            "DISK",
+            units.bytes(left.disk_total),
+            units.bytes(right.disk_total),
+        ),
+        Row::numeric("CPU%", live(|ws| Some(ws.cpu.usage)), |value| {
+            units.percent(value)
+        }),
+        Row::numeric(
+            "RAM%",
+            live(|ws| percent(ws.ram.used, ws.ram.total)),
+            |value| units.percent(value),
+        ),
+        Row::numeric(
+            "DISK%",
+            live(|ws| percent(ws.disk.used, ws.disk.total)),
+            |value| units.percent(value),
+        ),
+        Row::numeric("LOAD", live(|ws| Some(ws.load.load1)), |value| {
+            units.number(value)
+        }),
+        Row::numeric("DOWN", live(|ws| Some(ws.network.down as f64)), |value| {
+            units.rate(value)
+        }),
+        Row::numeric("UP", live(|ws| Some(ws.network.up as f64)), |value| {
+            units.rate(value)
+        }),
+        Row::numeric(
+            "CONN",
+            live(|ws| Some(f64::from(ws.connections.tcp + ws.connections.udp))),
+            |value| format!("{value:.0}"),
+        ),
+    ]
+}
+
+/// `/compare A B` 的消息, 以等宽表格对比两个节点, 不同的行以 `*` 标出
+pub async fn parse_ws_compare(
+    telegram_id: i64,
+    left: &str,
+    right: &str,
+) -> Result<String, ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes, units) =
+        tokio::try_join!(client.snapshot(), client.nodes(), load_units(telegram_id))?;
+
+    let left = lookup_node(&ws_data, &nodes, left)?;
+    let right = lookup_node(&ws_data, &nodes, right)?;
+    if left.uuid == right.uuid {
+        return Err(String::from("请选择两个不同的节点"));
+    }
+
+    // 离线节点只对比硬件信息
+    let live = |node: &ApiNodesData| {
+        ws_data
+            .data
+            .data
+            .get(&node.uuid)
+            .filter(|_| ws_data.data.online.contains(&node.uuid))
+    };
+
+    let rows = build_rows([(left, live(left)), (right, live(right))], &units);
+
+    let header = Row::text("", truncate(&left.name), truncate(&right.name));
+    let label_width = rows
+        .iter()
+        .map(|row| row.label.len())
+        .max()
+        .unwrap_or_default();
+    let left_width = rows
+        .iter()
+        .chain([&header])
+        .map(|row| display_width(&truncate(&row.left)))
+        .max()
+        .unwrap_or_default();
+
+    let mut table = String::new();
+    for row in [&header].into_iter().chain(&rows) {
+        let _ = writeln!(
+            table,
+            "{} {} │ {} │ {}",
+            if row.differs && !row.label.is_empty() {
+                '*'
+            } else {
+                ' '
+            },
+            pad(row.label, label_width),
+            pad(&truncate(&row.left), left_width),
+            truncate(&row.right)
+        );
+    }
+
+    let differences = rows.iter().filter(|row| row.differs).count();
+
+    let mut message = Markup::default();
+    message
+        .text(format!("节点对比 ({differences} 项不同，以 * 标出)"))
+        .line()
+        .pre(table);
+
+    Ok(message.build())
+}
+
+/// `/compare NODE_ID NODE_ID`
+pub async fn command(telegram_id: i64, left: Option<String>, right: Option<String>) -> Reply {
+    match (left, right) {
+        (Some(left), Some(right)) => Reply::formatted(
+            parse_ws_compare(telegram_id, &left, &right).await,
+            "无法对比节点",
+        ),
+        _ => Reply::Text(String::from("用法: /compare NODE_ID NODE_ID")),
+    }
+}
@@ -0,0 +1,121 @@
+use crate::ErrorString;
+use crate::Reply;
+use crate::callback::{CallbackAction, CallbackData, node_callback};
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::query::NodeQuery;
+use crate::connection::ws_get::status::sorted_node_uuids;
+use crate::markup::Markup;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+const PAGE_SIZE: usize = 10;
+
+/// `/find` 某一页的消息与节点按钮, 查询随翻页按钮传递
+pub async fn parse_ws_find(
+    telegram_id: i64,
+    query: &NodeQuery,
+    page: usize,
+) -> Result<(String, InlineKeyboardMarkup), ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let results: Vec<_> = sorted_node_uuids(&ws_data, &nodes)
+        .into_iter()
+        .zip(1..)
+        .filter_map(|(uuid, id)| {
+            let node = nodes.data.iter().find(|node| node.uuid == uuid)?;
+            query.matches(node).then_some((id, node))
+        })
+        .collect();
+
+    if results.is_empty() {
+        return Err(format!("没有符合条件的节点: {}", query.text()));
+    }
+
+    let pages = results.len().div_ceil(PAGE_SIZE);
+    let page = page.min(pages - 1);
+
+    let mut message = Markup::default();
+    message
+        .text("查询: ")
+        .code(query.text())
+        .line()
+        .text("共 ")
+        .code(results.len().to_string())
+        .text(" 个节点，第 ")
+        .code((page + 1).to_string())
+        .text(" / ")
+        .code(pages.to_string())
+        .text(" 页")
+        .line()
+        .line();
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+    for (id, node) in results.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+        let state = if ws_data.data.online.contains(&node.uuid) {
+            "🟢"
+        } else {
+            "🔴"
+        };
+
+        message
+            .code(id.to_string())
+            .text(format!(" - {state} {}", node.name))
+            .line()
+            .text(format!(
+                "{} / {} / {} / {}",
+                node.os, node.arch, node.virtualization, node.kernel_version
+            ))
+            .line();
+
+        let button = InlineKeyboardButton::callback(
+            format!("{state} {}", node.name),
+            node_callback(telegram_id, *id, None),
+        );
+        match keyboard.last_mut() {
+            Some(row) if row.len() < 2 => row.push(button),
+            _ => keyboard.push(vec![button]),
+        }
+    }
+
+    if pages > 1 {
+        let page_data = |page| {
+            CallbackData::new(CallbackAction::Find, telegram_id)
+                .page(page)
+                .arg(query.text())
+        };
+
+        let mut navigation = vec![];
+        if page > 0 {
+            navigation.push(("上一页", page_data(page - 1)));
+        }
+        if page + 1 < pages {
+            navigation.push(("下一页", page_data(page + 1)));
+        }
+
+        // 查询随按钮传递, 超出回调数据长度时无法翻页
+        if navigation.iter().all(|(_, data)| data.fits()) {
+            keyboard.push(
+                navigation
+                    .into_iter()
+                    .map(|(text, data)| InlineKeyboardButton::callback(text, data.encode()))
+                    .collect(),
+            );
+        } else {
+            message
+                .line()
+                .text("查询条件过长，无法翻页，请缩短查询条件");
+        }
+    }
+
+    Ok((message.build(), InlineKeyboardMarkup::new(keyboard)))
+}
+
+/// `/find 字段=值 ...`
+pub async fn command(telegram_id: i64, args: &[String]) -> Reply {
+    match NodeQuery::parse(args) {
+        Ok(query) => Reply::keyboard(parse_ws_find(telegram_id, &query, 0).await, "无法查找节点"),
+        Err(e) => Reply::Text(format!(
+            "{e}\n\n用法: /find 字段=值 ...\n运算符: = 包含, != 不包含, ~ 前缀, > >= < <= 数值比较\n字段: name os arch virt kernel cpu gpu region group tag mem swap disk cores price"
+        )),
+    }
+}
@@ -1,48 +1,124 @@
-use crate::connection::msg_fixer;
-use crate::connection::ws_get::status::sort_ws_data;
-use crate::connection::ws_get::{ApiWsDataHashMapValue, get_ws};
-use crate::{ErrorString, connection};
-use teloxide::prelude::Message;
-use tokio::task::JoinHandle;
-
-pub async fn ws_get_node_id(msg: Message) -> Result<String, ErrorString> {
-    let telegram_id = if let Some(user) = msg.clone().from {
-        user.id.0 as i64
-    } else {
-        return Err(String::from("无法获取用户ID"));
-    };
-
-    let ws_handle = tokio::spawn(async move { Ok(sort_ws_data(get_ws(telegram_id).await?)) });
-    let http_handle: JoinHandle<Result<connection::api_nodes::ApiNodes, ErrorString>> =
-        tokio::spawn(async move {
-            let nodes = connection::api_nodes::get_api_nodes(telegram_id).await?;
-            Ok(nodes)
-        });
-
-    let (sorted_ws_data, nodes): (
-        Result<Vec<(String, ApiWsDataHashMapValue)>, ErrorString>,
-        Result<connection::api_nodes::ApiNodes, ErrorString>,
-    ) = tokio::try_join!(ws_handle, http_handle)
-        .map_err(|e| format!("无法运行 Tokio 线程: {e}"))?;
-
-    let sorted_ws_data = sorted_ws_data?;
-    let nodes = nodes?;
-
-    let mut message_str = String::new();
-
-    let mut counter = 0;
-
-    for (node_uuid, _) in sorted_ws_data {
-        counter += 1;
-
-        let node = nodes
-            .data
-            .iter()
-            .find(|node| node.uuid == node_uuid)
-            .ok_or("找不到该序号的服务器")?;
-
-        message_str.push_str(&format!("`{}` - {}\n", counter, node.name));
+use crate::ErrorString;
+use crate::Reply;
+use crate::callback::{CallbackAction, CallbackData, node_callback};
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::filter::NodeFilter;
+use crate::connection::ws_get::status::sorted_node_uuids;
+use crate::markup::Markup;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+const PAGE_SIZE: usize = 20;
+
+/// 节点列表某一页的消息与节点按钮, 点击节点按钮打开其状态卡片
+///
+/// 筛选条件随翻页按钮传递, 旧消息的按钮不受之后的命令影响
+pub async fn ws_get_node_id(
+    telegram_id: i64,
+    filter: &NodeFilter,
+    page: usize,
+) -> Result<(String, InlineKeyboardMarkup), ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let results: Vec<_> = sorted_node_uuids(&ws_data, &nodes)
+        .into_iter()
+        .zip(1..)
+        .filter_map(|(uuid, id)| {
+            let node = nodes.data.iter().find(|node| node.uuid == uuid)?;
+            filter.matches(node).then_some((id, node))
+        })
+        .collect();
+
+    if results.is_empty() {
+        return Err(format!("没有符合筛选条件的节点: {}", filter.describe()));
+    }
+
+    let pages = results.len().div_ceil(PAGE_SIZE);
+    let page = page.min(pages - 1);
+    let online = results
+        .iter()
+        .filter(|(_, node)| ws_data.data.online.contains(&node.uuid))
+        .count();
+
+    let mut message = Markup::default();
+    if !filter.is_empty() {
+        message.text("筛选: ").code(filter.describe()).line();
+    }
+    message
+        .text("共 ")
+        .code(results.len().to_string())
+        .text(" 个节点，在线 ")
+        .code(online.to_string())
+        .text(" 个，第 ")
+        .code((page + 1).to_string())
+        .text(" / ")
+        .code(pages.to_string())
+        .text(" 页")
+        .line()
+        .text("点击节点查看状态");
+
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+    for (id, node) in results.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+        let state = if ws_data.data.online.contains(&node.uuid) {
+            "🟢"
+        } else {
+            "🔴"
+        };
+
+        // 带上分组, 使状态卡片只在该分组内翻页
+        let callback = node_callback(telegram_id, *id, filter.group.as_deref());
+        let button =
+            InlineKeyboardButton::callback(format!("{id}. {state} {}", node.name), callback);
+        match keyboard.last_mut() {
+            Some(row) if row.len() < 2 => row.push(button),
+            _ => keyboard.push(vec![button]),
+        }
     }
 
-    Ok(msg_fixer(message_str))
+    if pages > 1 {
+        let page_data = |page| {
+            let data = CallbackData::new(CallbackAction::Nodes, telegram_id).page(page);
+            if filter.is_empty() {
+                data
+            } else {
+                data.arg(&filter.describe())
+            }
+        };
+
+        let mut navigation = vec![];
+        if page > 0 {
+            navigation.push(("上一页", page_data(page - 1)));
+        }
+        if page + 1 < pages {
+            navigation.push(("下一页", page_data(page + 1)));
+        }
+
+        // 筛选条件随按钮传递, 超出回调数据长度时无法翻页
+        if navigation.iter().all(|(_, data)| data.fits()) {
+            keyboard.push(
+                navigation
+                    .into_iter()
+                    .map(|(text, data)| InlineKeyboardButton::callback(text, data.encode()))
+                    .collect(),
+            );
+        } else {
+            message
+                .line()
+                .text("筛选条件过长，无法翻页，请缩短筛选条件");
+        }
+    }
+
+    Ok((message.build(), InlineKeyboardMarkup::new(keyboard)))
+}
+
+/// `/get_node_id [筛选条件]`
+pub async fn command(telegram_id: i64, filter: &[String]) -> Reply {
+    match NodeFilter::parse(filter) {
+        Ok(filter) => Reply::keyboard(
+            ws_get_node_id(telegram_id, &filter, 0).await,
+            "无法获取节点ID",
+        ),
+        Err(e) => Reply::Text(e),
+    }
 }
@@ -0,0 +1,40 @@
+use crate::ErrorString;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::markup::Markup;
+use std::collections::BTreeMap;
+
+pub async fn parse_ws_groups(telegram_id: i64) -> Result<String, ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let monitor = client.monitor();
+
+    let mut groups: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for node in &nodes.data {
+        let (online, total) = groups.entry(node.group_name()).or_default();
+        *total += 1;
+        if ws_data.data.online.contains(&node.uuid) {
+            *online += 1;
+        }
+    }
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{} 分组", monitor.site_name))
+        .line()
+        .line();
+    for (group, (online, total)) in groups {
+        message
+            .text(format!("{group} - "))
+            .code(online.to_string())
+            .text(" / ")
+            .code(total.to_string())
+            .text(" 在线")
+            .line();
+    }
+    message
+        .line()
+        .text("使用 /total_status group=分组名 查看分组总览");
+
+    Ok(message.build())
+}
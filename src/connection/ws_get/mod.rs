@@ -1,16 +1,20 @@
+pub mod compare;
+pub mod find;
 pub mod get_node_id;
+pub mod groups;
+pub mod offline;
+pub mod regions;
 pub mod status;
+pub mod top;
 pub mod total_status;
 
 use crate::ErrorString;
-use crate::db::{DB_POOL, query_monitor_by_telegram_id};
-use futures::{SinkExt, StreamExt};
+use crate::connection::client::{KomariApi, KomariClient};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tokio::net::TcpStream;
 use tokio_tungstenite::tungstenite::handshake::client::{Request, generate_key};
-use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
 
 pub async fn connect_ws(
@@ -116,41 +120,5 @@ pub struct Connections {
 }
 
 pub async fn get_ws(telegram_id: i64) -> Result<ApiWs, ErrorString> {
-    let monitor = query_monitor_by_telegram_id(
-        DB_POOL
-            .get()
-            .unwrap_or_else(|| panic!("数据库连接池未初始化")),
-        telegram_id,
-    )
-    .await?;
-
-    let (http_url, ws_url) = if let Some(monitor) = monitor {
-        (monitor.monitor_http_url, monitor.monitor_ws_url)
-    } else {
-        return Err(ErrorString::from(
-            "服务器未连接，请先使用 /connect [http url] 连接".to_string(),
-        ));
-    };
-
-    let ws_connection = connect_ws(&http_url, &ws_url).await?;
-
-    let (mut write, mut read) = ws_connection.split();
-
-    write
-        .send(Message::Text(Utf8Bytes::from("get")))
-        .await
-        .map_err(|_| String::from("无法发送数据"))?;
-
-    let Some(Ok(msg)) = read.next().await else {
-        return Err(String::from("数据接收出现错误"));
-    };
-
-    let data_str = msg
-        .to_text()
-        .map_err(|_| String::from("无法将 Websocket 返回内容转化为文本"))?;
-
-    let data: ApiWs = serde_json::from_str(data_str)
-        .map_err(|e| format!("无法将 Websocket 响应内容转化为 JSON: {e}"))?;
-
-    Ok(data)
+    KomariClient::for_user(telegram_id).await?.snapshot().await
 }
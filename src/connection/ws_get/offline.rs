@@ -0,0 +1,56 @@
+use crate::ErrorString;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::ws_get::status::{last_seen, sorted_node_uuids};
+use crate::markup::{LIST_LIMIT, Markup};
+
+/// `/offline` 的消息, 列出 `/api/nodes` 中不在线的节点, 最多列出 [`LIST_LIMIT`] 个
+pub async fn parse_ws_offline(telegram_id: i64) -> Result<String, ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let mut list = Markup::default();
+    let mut offline_count = 0;
+
+    for (uuid, id) in sorted_node_uuids(&ws_data, &nodes).into_iter().zip(1..) {
+        if ws_data.data.online.contains(&uuid) {
+            continue;
+        }
+
+        let Some(node) = nodes.data.iter().find(|node| node.uuid == uuid) else {
+            continue;
+        };
+        offline_count += 1;
+        if offline_count > LIST_LIMIT {
+            continue;
+        }
+
+        list.code(id.to_string())
+            .text(format!(" - {}", node.name))
+            .line();
+        match last_seen(node) {
+            Some((last_seen, down_for)) => {
+                list.field("最后在线", last_seen).field("已离线", down_for)
+            }
+            None => list.field("最后在线", "未知"),
+        };
+        list.line();
+    }
+
+    let mut message = Markup::default();
+    if offline_count == 0 {
+        message.text(format!("全部 {} 个节点均在线", nodes.data.len()));
+        return Ok(message.build());
+    }
+
+    message
+        .text("离线节点: ")
+        .code(offline_count.to_string())
+        .text(" / ")
+        .code(nodes.data.len().to_string())
+        .line()
+        .line()
+        .append(&list)
+        .remaining(offline_count);
+
+    Ok(message.build())
+}
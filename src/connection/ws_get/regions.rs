@@ -0,0 +1,189 @@
+use crate::ErrorString;
+use crate::callback::{CallbackAction, CallbackData, node_callback};
+use crate::connection::api_nodes::ApiNodesData;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::ws_get::status::sorted_node_uuids;
+use crate::markup::Markup;
+use crate::units::load_units;
+use std::collections::BTreeMap;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+#[derive(Default)]
+struct RegionStats {
+    total: usize,
+    online: usize,
+    cpu_usage: f64,
+    net_up: u64,
+    net_down: u64,
+    total_up: u64,
+    total_down: u64,
+}
+
+fn region_name(node: &ApiNodesData) -> String {
+    match node.region.trim() {
+        "" => String::from("未知"),
+        region => region.to_string(),
+    }
+}
+
+/// 两位 ISO 国家代码转换为旗帜 emoji, 例如 `HK` -> `🇭🇰 HK`
+pub fn region_label(region: &str) -> String {
+    if region.len() == 2 && region.chars().all(|char| char.is_ascii_alphabetic()) {
+        let flag: String = region
+            .to_ascii_uppercase()
+            .chars()
+            .filter_map(|char| char::from_u32(0x1F1E6 + (char as u32 - 'A' as u32)))
+            .collect();
+        format!("{flag} {}", region.to_ascii_uppercase())
+    } else {
+        region.to_string()
+    }
+}
+
+/// 回调数据上限为 64 字节, 地区名称过长时不提供按钮
+fn region_callback(telegram_id: i64, region: &str) -> Option<String> {
+    let data = CallbackData::new(CallbackAction::Region, telegram_id).arg(region);
+    data.fits().then(|| data.encode())
+}
+
+/// `/regions` 的消息与地区按钮
+pub async fn parse_ws_regions(
+    telegram_id: i64,
+) -> Result<(String, InlineKeyboardMarkup), ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let monitor = client.monitor();
+
+    let mut regions: BTreeMap<String, RegionStats> = BTreeMap::new();
+    for node in &nodes.data {
+        let stats = regions.entry(region_name(node)).or_default();
+        stats.total += 1;
+
+        if !ws_data.data.online.contains(&node.uuid) {
+            continue;
+        }
+        stats.online += 1;
+
+        if let Some(data) = ws_data.data.data.get(&node.uuid) {
+            stats.cpu_usage += data.cpu.usage;
+            stats.net_up += data.network.up;
+            stats.net_down += data.network.down;
+            stats.total_up += data.network.total_up;
+            stats.total_down += data.network.total_down;
+        }
+    }
+
+    let units = load_units(telegram_id).await?;
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{} 地区", monitor.site_name))
+        .line()
+        .line();
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+    for (region, stats) in &regions {
+        let avg_cpu = if stats.online == 0 {
+            0.0
+        } else {
+            stats.cpu_usage / stats.online as f64
+        };
+
+        message
+            .text(region_label(region))
+            .line()
+            .text("ONLINE: ")
+            .code(stats.online.to_string())
+            .text(" / ")
+            .code(stats.total.to_string())
+            .line()
+            .field("AVG CPU", units.percent(avg_cpu))
+            .text("SPEED: ")
+            .code(units.rate(stats.net_down as f64))
+            .text(" / ")
+            .code(units.rate(stats.net_up as f64))
+            .line()
+            .text("NET: ")
+            .code(units.bytes(stats.total_down))
+            .text(" / ")
+            .code(units.bytes(stats.total_up))
+            .line()
+            .line();
+
+        if let Some(callback) = region_callback(telegram_id, region) {
+            let button = InlineKeyboardButton::callback(
+                format!("{} ({})", region_label(region), stats.total),
+                callback,
+            );
+            match keyboard.last_mut() {
+                Some(row) if row.len() < 3 => row.push(button),
+                _ => keyboard.push(vec![button]),
+            }
+        }
+    }
+
+    Ok((message.build(), InlineKeyboardMarkup::new(keyboard)))
+}
+
+/// 某个地区的节点列表, 点击节点打开状态卡片
+pub async fn parse_ws_region_nodes(
+    telegram_id: i64,
+    region: &str,
+) -> Result<(String, InlineKeyboardMarkup), ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    let ids: BTreeMap<String, i32> = sorted_node_uuids(&ws_data, &nodes)
+        .into_iter()
+        .zip(1..)
+        .collect();
+    let online = ws_data.data.online;
+
+    let mut region_nodes: Vec<&ApiNodesData> = nodes
+        .data
+        .iter()
+        .filter(|node| region_name(node) == region)
+        .collect();
+    if region_nodes.is_empty() {
+        return Err(format!("地区 {region} 中没有节点"));
+    }
+    region_nodes.sort_by_key(|node| ids[&node.uuid]);
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{} 的节点", region_label(region)))
+        .line()
+        .line();
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+    for node in region_nodes {
+        let state = if online.contains(&node.uuid) {
+            "🟢"
+        } else {
+            "🔴"
+        };
+
+        let id = ids[&node.uuid];
+        message
+            .code(id.to_string())
+            .text(format!(" - {state} {}", node.name))
+            .line();
+
+        let button = InlineKeyboardButton::callback(
+            format!("{state} {}", node.name),
+            node_callback(telegram_id, id, None),
+        );
+        match keyboard.last_mut() {
+            Some(row) if row.len() < 2 => row.push(button),
+            _ => keyboard.push(vec![button]),
+        }
+    }
+
+    keyboard.push(vec![InlineKeyboardButton::callback(
+        "返回地区列表",
+        CallbackData::new(CallbackAction::Regions, telegram_id).encode(),
+    )]);
+
+    Ok((message.build(), InlineKeyboardMarkup::new(keyboard)))
+}
@@ -1,74 +1,94 @@
 use crate::ErrorString;
-use crate::connection::api_nodes::{ApiNodes, get_api_nodes};
-use crate::connection::msg_fixer;
-use crate::connection::ws_get::{ApiWs, ApiWsDataHashMapValue, get_ws};
-use crate::db::{DB_POOL, query_monitor_by_telegram_id};
+use crate::Reply;
+use crate::callback::{CallbackAction, CallbackData};
+use crate::connection::api_nodes::{ApiNodes, ApiNodesData};
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::filter::NodeFilter;
+use crate::connection::ws_get::{ApiWs, ApiWsDataHashMapValue};
+use crate::expiry::{days_left, format_price};
+use crate::markup::Markup;
+use crate::schedule::{format_timestamp, now, parse_komari_time};
+use crate::template::{TemplateKind, TemplateValue, load_template};
+use crate::units::{Units, load_units};
 use reqwest::Url;
+use std::collections::HashMap;
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
-use tokio::task::JoinHandle;
 
-pub async fn ws_single_server_by_index(
-    telegram_id: i64,
-    index: i32,
-) -> Result<Option<(String, ApiWsDataHashMapValue)>, ErrorString> {
-    let ws_data = match get_ws(telegram_id).await {
-        Ok(ws_data) => ws_data,
-        Err(e) => {
-            return Err(format!("无法连接到 Komari Websocket 服务器: {e}"));
-        }
-    };
+/// Bot 序号 (从 1 开始): 实时数据中的节点按 uuid 排序在前, 只出现在 `/api/nodes` 中的离线节点按 uuid 排在其后,
+/// 因此离线节点不会改变在线节点原有的序号
+pub fn sorted_node_uuids(ws_data: &ApiWs, nodes: &ApiNodes) -> Vec<String> {
+    let mut uuids: Vec<String> = ws_data.data.data.keys().cloned().collect();
+    uuids.sort();
 
-    let sorted_data = sort_ws_data(ws_data);
+    let mut offline: Vec<String> = nodes
+        .data
+        .iter()
+        .filter(|node| !ws_data.data.data.contains_key(&node.uuid))
+        .map(|node| node.uuid.clone())
+        .collect();
+    offline.sort();
 
-    if index > sorted_data.len() as i32 {
-        return Ok(None);
-    }
+    uuids.extend(offline);
+    uuids
+}
 
-    let vec_index: usize = match index {
-        0 | 1 => 0,
-        _ => (index - 1) as usize,
-    };
+/// 按 Bot 序号查找节点 uuid, 序号 0 视为 1
+pub fn uuid_by_index(ws_data: &ApiWs, nodes: &ApiNodes, index: i32) -> Option<String> {
+    let vec_index = usize::try_from(index.max(1) - 1).ok()?;
+    sorted_node_uuids(ws_data, nodes).get(vec_index).cloned()
+}
 
-    Ok(sorted_data.get(vec_index).cloned())
+/// 单节点卡片的标签页, 回调数据中以页码表示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeTab {
+    Overview,
+    Network,
+    Hardware,
+    Recent,
 }
 
-pub fn sort_ws_data(ws_data: ApiWs) -> Vec<(String, ApiWsDataHashMapValue)> {
-    let mut sorted_data: Vec<(String, ApiWsDataHashMapValue)> =
-        ws_data.data.data.into_iter().collect();
-    sorted_data.sort_by(|a, b| a.0.cmp(&b.0));
-    sorted_data
+impl NodeTab {
+    const ALL: [Self; 4] = [Self::Overview, Self::Network, Self::Hardware, Self::Recent];
+
+    pub fn from_page(page: usize) -> Self {
+        Self::ALL.get(page).copied().unwrap_or(Self::Overview)
+    }
+
+    pub fn page(self) -> usize {
+        self as usize
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Overview => "概览",
+            Self::Network => "网络",
+            Self::Hardware => "硬件",
+            Self::Recent => "近期",
+        }
+    }
 }
 
 pub async fn parse_ws_single_server_by_index(
     telegram_id: i64,
     index: i32,
+    tab: NodeTab,
 ) -> Result<String, ErrorString> {
-    let ws_handle = tokio::spawn(async move {
-        let Some(node) = ws_single_server_by_index(telegram_id, index).await? else {
-            return Err(String::from("找不到该序号的服务器"));
-        };
-        Ok(node)
-    });
-    let http_handle: JoinHandle<Result<ApiNodes, ErrorString>> = tokio::spawn(async move {
-        let nodes = get_api_nodes(telegram_id).await?;
-        Ok(nodes)
-    });
-    let monitor = query_monitor_by_telegram_id(
-        DB_POOL
-            .get()
-            .unwrap_or_else(|| panic!("数据库连接池未初始化")),
-        telegram_id,
-    )
-    .await?
-    .ok_or(String::from(
-        "服务器未连接，请先使用 /connect [http url] [ws url] 连接",
-    ))?;
+    let client = KomariClient::for_user(telegram_id).await?;
+    single_server(&client, telegram_id, index, tab).await
+}
 
-    let (ws_data, nodes) = tokio::try_join!(ws_handle, http_handle)
-        .map_err(|e| format!("无法运行 Tokio 线程: {e}"))?;
+/// 按 Bot 序号显示单个节点卡片的某个标签页
+pub async fn single_server(
+    client: &impl KomariApi,
+    telegram_id: i64,
+    index: i32,
+    tab: NodeTab,
+) -> Result<String, ErrorString> {
+    let (ws_data, nodes) = tokio::join!(client.snapshot(), client.nodes());
     let nodes = nodes?;
+    let ws_data = ws_data?;
 
-    let (uuid, ws_data) = ws_data?;
+    let uuid = uuid_by_index(&ws_data, &nodes, index).ok_or("找不到该序号的服务器")?;
 
     let node = nodes
         .data
@@ -76,94 +96,224 @@ pub async fn parse_ws_single_server_by_index(
         .find(|node| node.uuid == uuid)
         .ok_or("找不到该序号的服务器")?;
 
-    let title = monitor.site_name;
-    let region = node.region.clone();
-    let name = node.name.clone();
-    let updated_at = node.updated_at.clone();
-    let processes = ws_data.process;
-
-    let cpu_name = node.cpu_name.clone();
-    let cpu_cores = node.cpu_cores;
-    let os = node.os.clone();
-    let kernel_version = node.kernel_version.clone();
-    let virtualization = node.virtualization.clone();
-    let arch = node.arch.clone();
-    let cpu_usage = ws_data.cpu.usage;
-
-    let gpu_name = node.gpu_name.clone();
-
-    let ram_total = ws_data.ram.total as f64 / 1024.0 / 1024.0;
-    let ram_used = ws_data.ram.used as f64 / 1024.0 / 1024.0;
-    let ram_usage = ram_used / ram_total * 100.0;
-
-    let swap_total = ws_data.swap.total as f64 / 1024.0 / 1024.0;
-    let swap_used = ws_data.swap.used as f64 / 1024.0 / 1024.0;
-    let swap_usage = swap_used / swap_total * 100.0;
-
-    let disk_total = ws_data.disk.total as f64 / 1024.0 / 1024.0 / 1024.0;
-    let disk_used = ws_data.disk.used as f64 / 1024.0 / 1024.0 / 1024.0;
-    let disk_usage = disk_used / disk_total * 100.0;
-
-    let uptime = format_duration(ws_data.uptime);
-    let load1 = ws_data.load.load1;
-    let load5 = ws_data.load.load5;
-    let load15 = ws_data.load.load15;
-
-    let total_net_down = ws_data.network.total_down as f64 / 1024.0 / 1024.0 / 1024.0;
-    let total_net_up = ws_data.network.total_up as f64 / 1024.0 / 1024.0 / 1024.0;
-    let net_down = ws_data.network.down as f64 / 125000.0;
-    let net_up = ws_data.network.up as f64 / 125000.0;
-
-    let total_tcp_connections = ws_data.connections.tcp;
-    let total_udp_connections = ws_data.connections.udp;
-
-    let source_str = format!(
-        r"{title} | {region} | {name}
-
-CPU: `{cpu_name}` @ `{cpu_cores} Cores`{}
-ARCH: `{arch}`
-VIRT: `{virtualization}`
-OS: `{os}`
-KERN: `{kernel_version}`
-UPTIME: `{uptime}`
-
-CPU: `{cpu_usage:.2}%`
-RAM: `{ram_used:.2}` / `{ram_total:.2} MB` `{ram_usage:.2}%`
-SWAP: `{swap_used:.2}` / `{swap_total:.2} MB` `{swap_usage:.2}%`
-DISK: `{disk_used:.2}` / `{disk_total:.2} GB` `{disk_usage:.2}%`
-
-LOAD: `{load1:.2}` / `{load5:.2}` / `{load15:.2}`
-PROC: `{processes}`
-
-NET: `{total_net_down:.2} GB` / `{total_net_up:.2} GB`
-UP: `{net_down:.2} Mbps`
-DOWN: `{net_up:.2} Mbps`
-CONN: `{total_tcp_connections} TCP` / `{total_udp_connections} UDP`{}",
-        {
-            if gpu_name.is_empty() {
-                String::new()
-            } else {
-                format!(
-                    "
-GPU: `{gpu_name}`"
-                )
-            }
-        },
-        {
-            match updated_at {
-                Some(updated_at) => {
-                    format!(
-                        "
-
-UPDATE AT: `{updated_at}`"
-                    )
-                }
-                None => String::new(),
-            }
+    let title = &client.monitor().site_name;
+
+    let online_data = ws_data
+        .data
+        .data
+        .get(&uuid)
+        .filter(|_| ws_data.data.online.contains(&uuid));
+
+    let units = load_units(telegram_id).await?;
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{title} | {} | {}", node.region, node.name))
+        .line()
+        .line();
+
+    match (tab, online_data) {
+        (NodeTab::Hardware, _) => hardware_tab(&mut message, node, &units),
+        (NodeTab::Recent, _) => recent_tab(&mut message, client, &uuid, &units).await,
+        (NodeTab::Overview, Some(ws_data)) => load_template(telegram_id, TemplateKind::Status)
+            .await?
+            .render(&status_values(node, ws_data), &units, &mut message),
+        (NodeTab::Network, Some(ws_data)) => network_tab(&mut message, ws_data, &units),
+        (_, None) => return Ok(offline_card(title, node, &units)),
+    }
+
+    if let Some(updated_at) = &node.updated_at {
+        message.line().line().text("UPDATE AT: ").code(updated_at);
+    }
+
+    Ok(message.build())
+}
+
+/// 已用量占总量的百分比, 总量为 0 时视为 0
+pub fn usage_percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64 * 100.0
+    }
+}
+
+fn status_values<'a>(
+    node: &ApiNodesData,
+    ws_data: &ApiWsDataHashMapValue,
+) -> HashMap<&'a str, TemplateValue> {
+    HashMap::from([
+        ("name", TemplateValue::Text(node.name.clone())),
+        ("region", TemplateValue::Text(node.region.clone())),
+        ("os", TemplateValue::Text(node.os.clone())),
+        ("arch", TemplateValue::Text(node.arch.clone())),
+        ("virt", TemplateValue::Text(node.virtualization.clone())),
+        ("kernel", TemplateValue::Text(node.kernel_version.clone())),
+        ("cpu_name", TemplateValue::Text(node.cpu_name.clone())),
+        ("gpu", TemplateValue::Text(node.gpu_name.clone())),
+        (
+            "cores",
+            TemplateValue::Count(u64::try_from(node.cpu_cores).unwrap_or_default()),
+        ),
+        ("uptime", TemplateValue::Duration(ws_data.uptime)),
+        ("cpu", TemplateValue::Percent(ws_data.cpu.usage)),
+        ("ram_used", TemplateValue::Bytes(ws_data.ram.used)),
+        ("ram_total", TemplateValue::Bytes(ws_data.ram.total)),
+        (
+            "ram_percent",
+            TemplateValue::Percent(usage_percent(ws_data.ram.used, ws_data.ram.total)),
+        ),
+        ("swap_used", TemplateValue::Bytes(ws_data.swap.used)),
+        ("swap_total", TemplateValue::Bytes(ws_data.swap.total)),
+        (
+            "swap_percent",
+            TemplateValue::Percent(usage_percent(ws_data.swap.used, ws_data.swap.total)),
+        ),
+        ("disk_used", TemplateValue::Bytes(ws_data.disk.used)),
+        ("disk_total", TemplateValue::Bytes(ws_data.disk.total)),
+        (
+            "disk_percent",
+            TemplateValue::Percent(usage_percent(ws_data.disk.used, ws_data.disk.total)),
+        ),
+        ("load1", TemplateValue::Number(ws_data.load.load1)),
+        ("load5", TemplateValue::Number(ws_data.load.load5)),
+        ("load15", TemplateValue::Number(ws_data.load.load15)),
+        ("process", TemplateValue::Count(ws_data.process.into())),
+        ("net_up", TemplateValue::Rate(ws_data.network.up)),
+        ("net_down", TemplateValue::Rate(ws_data.network.down)),
+        (
+            "net_total_up",
+            TemplateValue::Bytes(ws_data.network.total_up),
+        ),
+        (
+            "net_total_down",
+            TemplateValue::Bytes(ws_data.network.total_down),
+        ),
+        ("tcp", TemplateValue::Count(ws_data.connections.tcp.into())),
+        ("udp", TemplateValue::Count(ws_data.connections.udp.into())),
+    ])
+}
+
+fn network_tab(message: &mut Markup, ws_data: &ApiWsDataHashMapValue, units: &Units) {
+    message
+        .field("UP", units.rate(ws_data.network.up as f64))
+        .field("DOWN", units.rate(ws_data.network.down as f64))
+        .line()
+        .field("TOTAL UP", units.bytes(ws_data.network.total_up))
+        .field("TOTAL DOWN", units.bytes(ws_data.network.total_down))
+        .line()
+        .text("CONN: ")
+        .code(format!("{} TCP", ws_data.connections.tcp))
+        .text(" / ")
+        .code(format!("{} UDP", ws_data.connections.udp));
+}
+
+fn hardware_tab(message: &mut Markup, node: &ApiNodesData, units: &Units) {
+    message
+        .text("CPU: ")
+        .code(&node.cpu_name)
+        .text(" @ ")
+        .code(format!("{} Cores", node.cpu_cores))
+        .line();
+    if !node.gpu_name.is_empty() {
+        message.field("GPU", &node.gpu_name);
+    }
+    message
+        .field("ARCH", &node.arch)
+        .field("VIRT", &node.virtualization)
+        .field("OS", &node.os)
+        .field("KERN", &node.kernel_version)
+        .line()
+        .field("RAM", units.bytes(node.mem_total))
+        .field("SWAP", units.bytes(node.swap_total))
+        .field("DISK", units.bytes(node.disk_total))
+        .line()
+        .field("PRICE", format_price(node.price))
+        .text("EXPIRE: ");
+
+    let now = now();
+    match node.expired_at.as_deref().and_then(parse_komari_time) {
+        Some(expired_at) if expired_at > now => message
+            .code(format_timestamp(expired_at.timestamp()))
+            .text(" (")
+            .code(days_left(expired_at, now).to_string())
+            .text(" 天)"),
+        Some(expired_at) => message
+            .code(format_timestamp(expired_at.timestamp()))
+            .text(" (已过期)"),
+        None => message.code("未设置"),
+    };
+}
+
+fn min_max(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::MAX, f64::MIN), |(min, max), value| {
+        (min.min(value), max.max(value))
+    })
+}
+
+/// 最近一小时的最小值与最大值, 主控没有记录时给出提示
+async fn recent_tab(message: &mut Markup, client: &impl KomariApi, uuid: &str, units: &Units) {
+    let records = match client.records(uuid, 1).await {
+        Ok(records) => records.data.records,
+        Err(e) => {
+            message.text(format!("无法获取历史记录: {e}"));
+            return;
         }
-    );
+    };
+
+    if records.is_empty() {
+        message.text("最近一小时暂无历史记录");
+        return;
+    }
+
+    let (cpu_min, cpu_max) = min_max(records.iter().map(|record| record.cpu));
+    let (ram_min, ram_max) = min_max(records.iter().map(|record| record.ram as f64));
+    let (load_min, load_max) = min_max(records.iter().map(|record| record.load));
+    let (up_min, up_max) = min_max(records.iter().map(|record| record.net_out as f64));
+    let (down_min, down_max) = min_max(records.iter().map(|record| record.net_in as f64));
+    let (conn_min, conn_max) = min_max(records.iter().map(|record| record.connections as f64));
+
+    let range = |message: &mut Markup, label: &str, min: String, max: String| {
+        message
+            .text(format!("{label}: "))
+            .code(min)
+            .text(" ~ ")
+            .code(max)
+            .line();
+    };
 
-    Ok(msg_fixer(source_str))
+    message
+        .text("最近 1 小时 (")
+        .code(records.len().to_string())
+        .text(" 条记录, 最小 ~ 最大)")
+        .line()
+        .line();
+    range(
+        message,
+        "CPU",
+        units.percent(cpu_min),
+        units.percent(cpu_max),
+    );
+    range(
+        message,
+        "RAM",
+        units.bytes(ram_min as u64),
+        units.bytes(ram_max as u64),
+    );
+    range(
+        message,
+        "LOAD",
+        units.number(load_min),
+        units.number(load_max),
+    );
+    range(message, "UP", units.rate(up_min), units.rate(up_max));
+    range(message, "DOWN", units.rate(down_min), units.rate(down_max));
+    range(
+        message,
+        "TCP",
+        format!("{conn_min:.0}"),
+        format!("{conn_max:.0}"),
+    );
 }
 
 pub fn format_duration(mut seconds: u64) -> String {
@@ -193,52 +343,227 @@ pub fn format_duration(mut seconds: u64) -> String {
     result.join(" ")
 }
 
+/// 符合筛选条件的节点的 Bot 序号
+pub async fn filtered_node_ids(
+    telegram_id: i64,
+    filter: &NodeFilter,
+) -> Result<Vec<i32>, ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    Ok(sorted_node_uuids(&ws_data, &nodes)
+        .iter()
+        .zip(1..)
+        .filter(|(uuid, _)| {
+            nodes
+                .data
+                .iter()
+                .any(|node| &node.uuid == *uuid && filter.matches(node))
+        })
+        .map(|(_, id)| id)
+        .collect())
+}
+
+/// 最后在线时间与已离线时长, 无法解析 `updated_at` 时返回 `None`
+pub fn last_seen(node: &ApiNodesData) -> Option<(String, String)> {
+    let updated_at = parse_komari_time(node.updated_at.as_deref()?)?;
+    let down_for = (now() - updated_at).num_seconds().max(0);
+
+    Some((
+        format_timestamp(updated_at.timestamp()),
+        format_duration(down_for.unsigned_abs()),
+    ))
+}
+
+fn offline_card(title: &str, node: &ApiNodesData, units: &Units) -> String {
+    let (last_seen, down_for) =
+        last_seen(node).unwrap_or_else(|| (String::from("未知"), String::from("未知")));
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{title} | {} | {}", node.region, node.name))
+        .line()
+        .line()
+        .field("STATUS", "离线")
+        .field("LAST SEEN", last_seen)
+        .field("DOWN FOR", down_for)
+        .line()
+        .text("CPU: ")
+        .code(&node.cpu_name)
+        .text(" @ ")
+        .code(format!("{} Cores", node.cpu_cores))
+        .line()
+        .field("ARCH", &node.arch)
+        .field("VIRT", &node.virtualization)
+        .field("OS", &node.os)
+        .field("KERN", &node.kernel_version)
+        .field("RAM", units.bytes(node.mem_total))
+        .text("DISK: ")
+        .code(units.bytes(node.disk_total));
+
+    message.build()
+}
+
 pub async fn make_keyboard_for_single(
     now_id: i32,
     telegram_id: i64,
+    group: Option<&str>,
+    tab: NodeTab,
 ) -> Result<InlineKeyboardMarkup, ErrorString> {
-    let db_pool = DB_POOL
-        .get()
-        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
-    let monitor = query_monitor_by_telegram_id(db_pool, telegram_id)
-        .await?
-        .ok_or(String::from(
-            "服务器未连接，请先使用 /connect [http url] [ws url] 添加服务器",
-        ))?;
-    let max_server = monitor.total_server_count;
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+    // 编号包括排在在线节点之后的离线节点
+    let max_server = i32::try_from(sorted_node_uuids(&ws_data, &nodes).len()).unwrap_or(i32::MAX);
+
+    // 回调数据上限为 64 字节, 分组名称过长时退化为全局翻页
+    let group = group.filter(|group| {
+        CallbackData::new(CallbackAction::Node, telegram_id)
+            .node(max_server)
+            .page(NodeTab::Recent.page())
+            .arg(group)
+            .fits()
+    });
 
     let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
     let mut first_row = vec![];
 
-    let send_id = match now_id {
-        0 | 1 => (0, 2),
-        _ => (now_id - 1, now_id + 1),
+    // 翻页与刷新保持当前标签页
+    let callback = |id: i32, tab: NodeTab| {
+        let data = CallbackData::new(CallbackAction::Node, telegram_id)
+            .node(id)
+            .page(tab.page());
+        match group {
+            Some(group) => data.arg(group).encode(),
+            None => data.encode(),
+        }
+    };
+
+    let (send_id, position) = if let Some(group) = group {
+        // 仅在同一分组内翻页
+        let filter = NodeFilter {
+            group: Some(group.to_string()),
+            tag: None,
+        };
+        let ids = filtered_node_ids(telegram_id, &filter).await?;
+        let prev = ids.iter().rev().find(|id| **id < now_id).copied();
+        let next = ids.iter().find(|id| **id > now_id).copied();
+        let index = ids.iter().position(|id| *id == now_id).map_or(0, |i| i + 1);
+
+        (
+            (prev.unwrap_or(0), next.unwrap_or(i32::MAX)),
+            format!("{group} {index} / {}", ids.len()),
+        )
+    } else {
+        (
+            match now_id {
+                0 | 1 => (0, 2),
+                _ => (now_id - 1, now_id + 1),
+            },
+            format!("{now_id} / {max_server}"),
+        )
     };
 
     if send_id.0 > 0 {
         first_row.push(InlineKeyboardButton::callback(
             "<-",
-            format!("{}-{}", telegram_id, send_id.0),
+            callback(send_id.0, tab),
         ));
     }
 
     first_row.push(InlineKeyboardButton::url(
-        format!("{now_id} / {max_server}"),
+        position,
         Url::parse("https://t.me/komaritgbot").unwrap(),
     ));
 
-    if send_id.1 <= max_server as i32 {
+    if send_id.1 <= max_server {
         first_row.push(InlineKeyboardButton::callback(
             "->",
-            format!("{}-{}", telegram_id, send_id.1),
+            callback(send_id.1, tab),
         ));
     }
 
     keyboard.push(first_row);
+    keyboard.push(
+        NodeTab::ALL
+            .iter()
+            .map(|item| {
+                let text = if *item == tab {
+                    format!("· {} ·", item.title())
+                } else {
+                    item.title().to_string()
+                };
+                InlineKeyboardButton::callback(text, callback(now_id, *item))
+            })
+            .collect(),
+    );
     keyboard.push(vec![InlineKeyboardButton::callback(
         "Refresh",
-        format!("{}-{}", telegram_id, now_id),
+        callback(now_id, tab),
     )]);
 
     Ok(InlineKeyboardMarkup::new(keyboard))
 }
+
+/// `/status [NODE_ID]` 或 `/status group=GROUP`, 未指定节点时显示第一个
+pub async fn command(telegram_id: i64, node_id: Option<i32>, group: Option<String>) -> Reply {
+    let node_id = match (node_id, &group) {
+        (Some(node_id), _) => node_id,
+        (None, None) => 1,
+        (None, Some(group)) => {
+            let filter = NodeFilter {
+                group: Some(group.clone()),
+                tag: None,
+            };
+
+            match filtered_node_ids(telegram_id, &filter).await {
+                Ok(ids) if !ids.is_empty() => ids[0],
+                Ok(_) => return Reply::Text(format!("分组 {group} 中没有节点")),
+                Err(e) => return Reply::Text(format!("无法获取分组节点: {e}")),
+            }
+        }
+    };
+
+    let card = match parse_ws_single_server_by_index(telegram_id, node_id, NodeTab::Overview).await
+    {
+        Ok(card) => card,
+        Err(e) => return Reply::Text(format!("无法解析 Komari 数据: {e}")),
+    };
+
+    Reply::keyboard(
+        make_keyboard_for_single(node_id, telegram_id, group.as_deref(), NodeTab::Overview)
+            .await
+            .map(|keyboard| (card, keyboard)),
+        "无法生成键盘",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_nodes_do_not_renumber_live_nodes() {
+        let ws_data: ApiWs =
+            serde_json::from_str(include_str!("../../../fixtures/komari/clients.json")).unwrap();
+        let mut nodes: ApiNodes =
+            serde_json::from_str(include_str!("../../../fixtures/komari/nodes.json")).unwrap();
+        let live = "0a4c6f1e-2b3d-4e5f-8a9b-0c1d2e3f4a5b";
+
+        // 按 uuid 排序时排在实时节点之前的离线节点
+        let mut offline = nodes.data[0].clone();
+        offline.uuid = String::from("00000000-0000-4000-8000-000000000000");
+        nodes.data.push(offline);
+
+        let uuids = sorted_node_uuids(&ws_data, &nodes);
+        assert_eq!(uuids.len(), 3);
+        assert_eq!(uuids[0], live);
+        assert_eq!(uuid_by_index(&ws_data, &nodes, 1).as_deref(), Some(live));
+        assert_eq!(
+            uuids[1..],
+            [
+                "00000000-0000-4000-8000-000000000000",
+                "7f3e9d2c-1b0a-4f8e-9d7c-6b5a4f3e2d1c"
+            ]
+        );
+    }
+}
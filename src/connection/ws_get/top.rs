@@ -0,0 +1,153 @@
+use crate::ErrorString;
+use crate::Reply;
+use crate::callback::node_callback;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::ws_get::ApiWsDataHashMapValue;
+use crate::connection::ws_get::status::sorted_node_uuids;
+use crate::markup::Markup;
+use crate::units::{Units, load_units};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+const TOP_COUNT: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopMetric {
+    Cpu,
+    Ram,
+    Disk,
+    Net,
+    Conn,
+    Load,
+}
+
+impl TopMetric {
+    pub fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "cpu" => Some(Self::Cpu),
+            "ram" | "mem" => Some(Self::Ram),
+            "disk" => Some(Self::Disk),
+            "net" => Some(Self::Net),
+            "conn" => Some(Self::Conn),
+            "load" => Some(Self::Load),
+            _ => None,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Ram => "RAM",
+            Self::Disk => "DISK",
+            Self::Net => "NET",
+            Self::Conn => "CONN",
+            Self::Load => "LOAD",
+        }
+    }
+
+    fn value(self, data: &ApiWsDataHashMapValue) -> f64 {
+        let percent = |used: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                used as f64 / total as f64 * 100.0
+            }
+        };
+
+        match self {
+            Self::Cpu => data.cpu.usage,
+            Self::Ram => percent(data.ram.used, data.ram.total),
+            Self::Disk => percent(data.disk.used, data.disk.total),
+            Self::Net => (data.network.up + data.network.down) as f64,
+            Self::Conn => f64::from(data.connections.tcp + data.connections.udp),
+            Self::Load => data.load.load1,
+        }
+    }
+
+    fn format(self, data: &ApiWsDataHashMapValue, units: &Units) -> String {
+        let value = self.value(data);
+        match self {
+            Self::Cpu | Self::Ram | Self::Disk => units.percent(value),
+            Self::Net => format!(
+                "↓ {} / ↑ {}",
+                units.rate(data.network.down as f64),
+                units.rate(data.network.up as f64)
+            ),
+            Self::Conn => format!(
+                "{} TCP / {} UDP",
+                data.connections.tcp, data.connections.udp
+            ),
+            Self::Load => format!(
+                "{} / {} / {}",
+                units.number(data.load.load1),
+                units.number(data.load.load5),
+                units.number(data.load.load15)
+            ),
+        }
+    }
+}
+
+/// `/top METRIC` 的消息与打开节点卡片的按钮
+pub async fn parse_ws_top(
+    telegram_id: i64,
+    metric: TopMetric,
+) -> Result<(String, InlineKeyboardMarkup), ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes, units) =
+        tokio::try_join!(client.snapshot(), client.nodes(), load_units(telegram_id))?;
+
+    let mut ranked: Vec<(i32, String, &ApiWsDataHashMapValue)> =
+        sorted_node_uuids(&ws_data, &nodes)
+            .into_iter()
+            .zip(1..)
+            .filter(|(uuid, _)| ws_data.data.online.contains(uuid))
+            .filter_map(|(uuid, id)| {
+                let data = ws_data.data.data.get(&uuid)?;
+                let name = nodes
+                    .data
+                    .iter()
+                    .find(|node| node.uuid == uuid)
+                    .map_or(uuid, |node| node.name.clone());
+                Some((id, name, data))
+            })
+            .collect();
+
+    if ranked.is_empty() {
+        return Err(String::from("当前没有在线节点"));
+    }
+
+    ranked.sort_by(|a, b| metric.value(b.2).total_cmp(&metric.value(a.2)));
+    ranked.truncate(TOP_COUNT);
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{} TOP {}", metric.title(), ranked.len()))
+        .line()
+        .line();
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
+
+    for (rank, (id, name, data)) in ranked.iter().enumerate() {
+        message
+            .text(format!("{}. {name} ", rank + 1))
+            .code(metric.format(data, &units))
+            .line();
+
+        let button = InlineKeyboardButton::callback(
+            format!("{}. {name}", rank + 1),
+            node_callback(telegram_id, *id, None),
+        );
+        match keyboard.last_mut() {
+            Some(row) if row.len() < 2 => row.push(button),
+            _ => keyboard.push(vec![button]),
+        }
+    }
+
+    Ok((message.build(), InlineKeyboardMarkup::new(keyboard)))
+}
+
+/// `/top METRIC`
+pub async fn command(telegram_id: i64, metric: Option<&str>) -> Reply {
+    match metric.and_then(TopMetric::parse) {
+        Some(metric) => Reply::keyboard(parse_ws_top(telegram_id, metric).await, "无法获取排行"),
+        None => Reply::Text(String::from("用法: /top cpu|ram|disk|net|conn|load")),
+    }
+}
@@ -1,207 +1,215 @@
-use crate::connection::msg_fixer;
-use crate::connection::ws_get::{get_ws, ApiWs};
-use crate::db::{query_monitor_by_telegram_id, DB_POOL};
-use crate::{connection, ErrorString};
-use tokio::task::JoinHandle;
-
-pub async fn parse_ws_total_status(telegram_id: i64) -> Result<String, ErrorString> {
-    let ws_handle = tokio::spawn(async move {
-        let ws_data = match get_ws(telegram_id).await {
-            Ok(ws_data) => ws_data,
-            Err(e) => {
-                return Err(format!("无法连接到 Komari Websocket 服务器: {e}"));
-            }
-        };
-        Ok(ws_data)
-    });
-
-    let http_handle: JoinHandle<Result<connection::api_nodes::ApiNodes, ErrorString>> =
-        tokio::spawn(async move {
-            let nodes = connection::api_nodes::get_api_nodes(telegram_id).await?;
-            Ok(nodes)
-        });
-
-    let (ws_data, nodes): (
-        Result<ApiWs, ErrorString>,
-        Result<connection::api_nodes::ApiNodes, ErrorString>,
-    ) = tokio::try_join!(ws_handle, http_handle)
-        .map_err(|e| format!("无法运行 Tokio 线程: {e}"))?;
+use crate::ErrorString;
+use crate::Reply;
+use crate::connection::api_nodes::ApiNodes;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::filter::NodeFilter;
+use crate::connection::ws_get::status::usage_percent;
+use crate::connection::ws_get::{ApiWs, ApiWsDataHashMapValue};
+use crate::db::Monitor;
+use crate::markup::Markup;
+use crate::template::{TemplateKind, TemplateValue, load_template};
+use crate::units::load_units;
+use std::collections::HashMap;
+
+fn count(len: usize) -> u32 {
+    u32::try_from(len).unwrap_or(u32::MAX)
+}
 
-    let ws_data = ws_data?;
-    let nodes = nodes?;
+/// 分母为 0 时显示为 "-", 而不是 NaN
+fn ratio(value: f64, count: u32, wrap: fn(f64) -> TemplateValue) -> TemplateValue {
+    if count == 0 {
+        TemplateValue::Text(String::new())
+    } else {
+        wrap(value / f64::from(count))
+    }
+}
 
-    let monitor = query_monitor_by_telegram_id(
-        DB_POOL
-            .get()
-            .unwrap_or_else(|| panic!("数据库连接池未初始化")),
+pub async fn parse_ws_total_status(
+    telegram_id: i64,
+    filter: &NodeFilter,
+) -> Result<String, ErrorString> {
+    total_status(
+        &KomariClient::for_user(telegram_id).await?,
         telegram_id,
+        filter,
     )
-    .await?
-    .ok_or(String::from(
-        "服务器未连接，请先使用 /connect [http url] 连接",
-    ))?;
+    .await
+}
+
+/// 所有节点的汇总卡片, 使用该用户的模板与单位设置
+pub async fn total_status(
+    client: &impl KomariApi,
+    telegram_id: i64,
+    filter: &NodeFilter,
+) -> Result<String, ErrorString> {
+    let (ws_data, nodes) = tokio::join!(client.snapshot(), client.nodes());
+    let ws_data = ws_data?;
+
+    overview(client.monitor(), ws_data, nodes?, telegram_id, filter).await
+}
+
+/// 以已获取的一帧实时数据与节点列表生成汇总卡片
+pub async fn overview(
+    monitor: &Monitor,
+    mut ws_data: ApiWs,
+    mut nodes: ApiNodes,
+    telegram_id: i64,
+    filter: &NodeFilter,
+) -> Result<String, ErrorString> {
+    if !filter.is_empty() {
+        nodes.data.retain(|node| filter.matches(node));
+        if nodes.data.is_empty() {
+            return Err(format!("没有符合筛选条件的节点: {}", filter.describe()));
+        }
+
+        let in_filter = |uuid: &String| nodes.data.iter().any(|node| &node.uuid == uuid);
+        ws_data.data.data.retain(|uuid, _| in_filter(uuid));
+        ws_data.data.online.retain(|uuid| in_filter(uuid));
+    }
+
+    let total_nodes_count = if filter.is_empty() {
+        monitor.total_server_count
+    } else {
+        count(nodes.data.len())
+    };
+    let values = overview_values(&ws_data, &nodes, total_nodes_count);
+
+    let title = if filter.is_empty() {
+        monitor.site_name.clone()
+    } else {
+        format!("{} ({})", monitor.site_name, filter.describe())
+    };
+
+    let template = load_template(telegram_id, TemplateKind::Overview).await?;
+    let units = load_units(telegram_id).await?;
+
+    let mut message = Markup::default();
+    message.text(format!("{title} 总览")).line().line();
+    template.render(&values, &units, &mut message);
+
+    Ok(message.build())
+}
 
-    let online_nodes_count = ws_data.data.online.len();
-    let total_nodes_count = monitor.total_server_count;
+/// 总览模板中的各项数值
+fn overview_values(
+    ws_data: &ApiWs,
+    nodes: &ApiNodes,
+    total_nodes_count: u32,
+) -> HashMap<&'static str, TemplateValue> {
+    let online_nodes_count = count(ws_data.data.online.len());
 
     let cores_count = nodes.data.iter().map(|node| node.cpu_cores).sum::<i32>();
 
-    let percent_online = online_nodes_count as f64 / f64::from(total_nodes_count) * 100.0;
-
-    let avg_cpu_usage = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.cpu.usage)
-        .sum::<f64>()
-        / ws_data.data.data.len() as f64;
-
-    let total_total_ram = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.ram.total)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let total_used_ram = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.ram.used)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let avg_ram_usage = total_used_ram / total_total_ram * 100.0;
-
-    let total_total_swap = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.swap.total)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let total_used_swap = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.swap.used)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let avg_swap_usage = total_used_swap / total_total_swap * 100.0;
-
-    let total_total_disk = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.disk.total)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let total_used_disk = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.disk.used)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let avg_disk_usage = total_used_disk / total_total_disk * 100.0;
-
-    let avg_load1 = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.load.load1)
-        .sum::<f64>()
-        / ws_data.data.data.len() as f64;
-    let avg_load5 = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.load.load5)
-        .sum::<f64>()
-        / ws_data.data.data.len() as f64;
-    let avg_load15 = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.load.load15)
-        .sum::<f64>()
-        / ws_data.data.data.len() as f64;
-
-    let total_total_net_down = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.network.total_down)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let total_total_net_up = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.network.total_up)
-        .sum::<u64>() as f64
-        / 1024.0
-        / 1024.0
-        / 1024.0;
-    let total_net_down = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.network.down)
-        .sum::<u64>() as f64
-        / 125000.0;
-    let total_net_up = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.network.up)
-        .sum::<u64>() as f64
-        / 125000.0;
-
-    let total_tcp_connections = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.connections.tcp)
-        .sum::<u32>();
-    let total_udp_connections = ws_data
-        .data
-        .data
-        .values()
-        .map(|node| node.connections.udp)
-        .sum::<u32>();
-
-    let title = monitor.site_name;
-
-    Ok(msg_fixer(format!(
-        r"{title} 总览
-
-ONLINE: `{online_nodes_count}` / `{total_nodes_count}` `{percent_online:.2}%`
-CPU CORES: `{cores_count}`
-AVG CPU: `{avg_cpu_usage:.2}%`
-AVG LOAD: `{avg_load1:.2}` / `{avg_load5:.2}` / `{avg_load15:.2}`
-
-MEM: `{total_used_ram:.2} GB` / `{total_total_ram:.2} GB` `{avg_ram_usage:.2}%`
-SWAP: `{total_used_swap:.2} GB` / `{total_total_swap:.2} GB` `{avg_swap_usage:.2}%`
-DISK: `{total_used_disk:.2} GB` / `{total_total_disk:.2} GB` `{avg_disk_usage:.2}%`
-
-DOWN: `{total_total_net_down:.2} GB`
-UP: `{total_total_net_up:.2} GB`
-DOWN SPEED: `{total_net_down:.2} Mbps`
-UP SPEED: `{total_net_up:.2} Mbps`
-CONN: `{total_tcp_connections} TCP` / `{total_udp_connections} UDP`"
-    )))
+    let nodes_data = ws_data.data.data.values();
+    let node_count = count(nodes_data.len());
+    let sum = |value: fn(&ApiWsDataHashMapValue) -> u64| nodes_data.clone().map(value).sum::<u64>();
+    let avg = |value: fn(&ApiWsDataHashMapValue) -> f64, wrap| {
+        ratio(nodes_data.clone().map(value).sum(), node_count, wrap)
+    };
+
+    let ram_used = sum(|node| node.ram.used);
+    let ram_total = sum(|node| node.ram.total);
+    let swap_used = sum(|node| node.swap.used);
+    let swap_total = sum(|node| node.swap.total);
+    let disk_used = sum(|node| node.disk.used);
+    let disk_total = sum(|node| node.disk.total);
+
+    HashMap::from([
+        ("online", TemplateValue::Count(online_nodes_count.into())),
+        ("total", TemplateValue::Count(total_nodes_count.into())),
+        (
+            "online_percent",
+            ratio(
+                f64::from(online_nodes_count) * 100.0,
+                total_nodes_count,
+                TemplateValue::Percent,
+            ),
+        ),
+        (
+            "cores",
+            TemplateValue::Count(u64::try_from(cores_count).unwrap_or_default()),
+        ),
+        ("cpu", avg(|node| node.cpu.usage, TemplateValue::Percent)),
+        ("load1", avg(|node| node.load.load1, TemplateValue::Number)),
+        ("load5", avg(|node| node.load.load5, TemplateValue::Number)),
+        (
+            "load15",
+            avg(|node| node.load.load15, TemplateValue::Number),
+        ),
+        ("ram_used", TemplateValue::Bytes(ram_used)),
+        ("ram_total", TemplateValue::Bytes(ram_total)),
+        (
+            "ram_percent",
+            TemplateValue::Percent(usage_percent(ram_used, ram_total)),
+        ),
+        ("swap_used", TemplateValue::Bytes(swap_used)),
+        ("swap_total", TemplateValue::Bytes(swap_total)),
+        (
+            "swap_percent",
+            TemplateValue::Percent(usage_percent(swap_used, swap_total)),
+        ),
+        ("disk_used", TemplateValue::Bytes(disk_used)),
+        ("disk_total", TemplateValue::Bytes(disk_total)),
+        (
+            "disk_percent",
+            TemplateValue::Percent(usage_percent(disk_used, disk_total)),
+        ),
+        ("net_up", TemplateValue::Rate(sum(|node| node.network.up))),
+        (
+            "net_down",
+            TemplateValue::Rate(sum(|node| node.network.down)),
+        ),
+        (
+            "net_total_up",
+            TemplateValue::Bytes(sum(|node| node.network.total_up)),
+        ),
+        (
+            "net_total_down",
+            TemplateValue::Bytes(sum(|node| node.network.total_down)),
+        ),
+        (
+            "tcp",
+            TemplateValue::Count(sum(|node| node.connections.tcp.into())),
+        ),
+        (
+            "udp",
+            TemplateValue::Count(sum(|node| node.connections.udp.into())),
+        ),
+    ])
+}
+
+/// `/total_status [筛选条件]`
+pub async fn command(telegram_id: i64, filter: &[String]) -> Reply {
+    match NodeFilter::parse(filter) {
+        Ok(filter) => Reply::formatted(
+            parse_ws_total_status(telegram_id, &filter).await,
+            "无法解析 Komari Websocket 数据",
+        ),
+        Err(e) => Reply::Text(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_fleet_has_no_nan() {
+        let mut ws_data: ApiWs =
+            serde_json::from_str(include_str!("../../../fixtures/komari/clients.json")).unwrap();
+        ws_data.data.data.clear();
+        ws_data.data.online.clear();
+        let mut nodes: ApiNodes =
+            serde_json::from_str(include_str!("../../../fixtures/komari/nodes.json")).unwrap();
+        nodes.data.clear();
+
+        let values = overview_values(&ws_data, &nodes, 0);
+        for key in ["online_percent", "cpu", "load1", "load5", "load15"] {
+            assert!(
+                matches!(&values[key], TemplateValue::Text(text) if text.is_empty()),
+                "{key}: {:?}",
+                values[key]
+            );
+        }
+    }
 }
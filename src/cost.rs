@@ -0,0 +1,267 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::get_api_nodes;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::lookup_node;
+use crate::db::{
+    DB_POOL, NodeBilling, delete_exchange_rate, delete_node_billing, query_base_currency,
+    query_exchange_rates, query_node_billings, upsert_base_currency, upsert_exchange_rate,
+    upsert_node_billing,
+};
+use crate::markup::Markup;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+const DEFAULT_BASE_CURRENCY: &str = "CNY";
+
+fn parse_cycle(cycle: &str) -> Option<i64> {
+    match cycle {
+        "month" | "monthly" => Some(1),
+        "quarter" | "quarterly" => Some(3),
+        "half" | "semiannual" => Some(6),
+        "year" | "yearly" | "annual" => Some(12),
+        _ => cycle
+            .trim_end_matches('m')
+            .parse::<i64>()
+            .ok()
+            .filter(|months| *months > 0),
+    }
+}
+
+fn format_cycle(cycle_months: i64) -> String {
+    match cycle_months {
+        1 => String::from("月付"),
+        3 => String::from("季付"),
+        6 => String::from("半年付"),
+        12 => String::from("年付"),
+        months => format!("{months} 个月"),
+    }
+}
+
+async fn base_currency(telegram_id: i64) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    Ok(query_base_currency(db_pool, telegram_id)
+        .await?
+        .unwrap_or_else(|| DEFAULT_BASE_CURRENCY.to_string()))
+}
+
+fn push_breakdown(message: &mut Markup, title: &str, breakdown: &BTreeMap<String, f64>) {
+    message.line().text(format!("{title}:")).line();
+    for (name, monthly) in breakdown {
+        message
+            .text(format!("{name} - "))
+            .code(format!("{monthly:.2}"))
+            .text(" / 月")
+            .line();
+    }
+}
+
+/// `/cost` 的消息
+pub async fn cost(telegram_id: i64) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let nodes = get_api_nodes(telegram_id).await?;
+    let base = base_currency(telegram_id).await?;
+    let billings: HashMap<String, NodeBilling> = query_node_billings(db_pool, telegram_id)
+        .await?
+        .into_iter()
+        .map(|billing| (billing.node_uuid.clone(), billing))
+        .collect();
+    let rates: HashMap<String, f64> = query_exchange_rates(db_pool, telegram_id)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut total_monthly = 0.0;
+    let mut priced = 0;
+    let mut missing_rates = BTreeSet::new();
+    let mut by_group = BTreeMap::new();
+    let mut by_region = BTreeMap::new();
+    let mut by_tag = BTreeMap::new();
+
+    for node in &nodes.data {
+        let Some(price) = node.price.filter(|price| *price > 0.0) else {
+            continue;
+        };
+
+        let (cycle_months, currency) = billings.get(&node.uuid).map_or_else(
+            || {
+                (
+                    node.billing_cycle_months().unwrap_or(1),
+                    node.currency_code().unwrap_or_else(|| base.clone()),
+                )
+            },
+            |billing| (billing.cycle_months, billing.currency.clone()),
+        );
+
+        let rate = if currency == base {
+            1.0
+        } else if let Some(rate) = rates.get(&currency) {
+            *rate
+        } else {
+            missing_rates.insert(currency);
+            continue;
+        };
+
+        let monthly = price / cycle_months as f64 * rate;
+        total_monthly += monthly;
+        priced += 1;
+
+        *by_group.entry(node.group_name()).or_insert(0.0) += monthly;
+        *by_region.entry(node.region.clone()).or_insert(0.0) += monthly;
+        for tag in node.tag_list() {
+            *by_tag.entry(tag).or_insert(0.0) += monthly;
+        }
+    }
+
+    let mut message = Markup::default();
+    message
+        .text(format!("费用统计 ({base})"))
+        .line()
+        .line()
+        .field("月均", format!("{total_monthly:.2}"))
+        .field("年均", format!("{:.2}", total_monthly * 12.0))
+        .text("已计价节点: ")
+        .code(priced.to_string())
+        .text(" / ")
+        .code(nodes.data.len().to_string())
+        .line();
+
+    if priced > 0 {
+        push_breakdown(&mut message, "按分组", &by_group);
+        push_breakdown(&mut message, "按地区", &by_region);
+        if !by_tag.is_empty() {
+            push_breakdown(&mut message, "按标签", &by_tag);
+        }
+    }
+
+    if !missing_rates.is_empty() {
+        message.line().text(format!(
+            "缺少汇率，未计入: {}\n请使用 /rate 设置",
+            missing_rates.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    Ok(message.build())
+}
+
+/// `/billing NODE month|quarter|year|N CURRENCY` 或 `/billing NODE off`, 覆盖主控中的计费周期与货币
+pub async fn billing(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = "用法:\n/billing - 查看节点计费设置\n/billing NODE_ID month|quarter|half|year|N [货币]\n/billing NODE_ID off";
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let Some(node) = args.first() else {
+        let billings = query_node_billings(db_pool, telegram_id).await?;
+        if billings.is_empty() {
+            return Ok(format!(
+                "未设置计费信息，按主控中的计费周期与货币计价，主控未填写时按月付、{} 计价\n\n{usage}",
+                base_currency(telegram_id).await?
+            ));
+        }
+
+        let mut message = String::from("节点计费设置:\n");
+        for billing in billings {
+            let _ = writeln!(
+                message,
+                "{} - {} {}",
+                billing.node_name,
+                format_cycle(billing.cycle_months),
+                billing.currency
+            );
+        }
+        return Ok(message);
+    };
+
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+    let node = lookup_node(&ws_data, &nodes, node)?;
+    let (node_uuid, node_name) = (node.uuid.clone(), node.name.clone());
+
+    let cycle = args.get(1).ok_or(usage)?;
+    if cycle == "off" {
+        return match delete_node_billing(db_pool, telegram_id, &node_uuid).await? {
+            0 => Err(format!("{node_name} 未设置计费信息")),
+            _ => Ok(format!("已删除 {node_name} 的计费信息")),
+        };
+    }
+
+    let cycle_months = parse_cycle(cycle).ok_or(usage)?;
+    let currency = match (args.get(2), node.currency_code()) {
+        (Some(currency), _) => currency.to_uppercase(),
+        (None, Some(currency)) => currency,
+        (None, None) => base_currency(telegram_id).await?,
+    };
+
+    upsert_node_billing(
+        db_pool,
+        telegram_id,
+        NodeBilling {
+            node_uuid,
+            node_name: node_name.clone(),
+            cycle_months,
+            currency: currency.clone(),
+        },
+    )
+    .await?;
+
+    Ok(format!(
+        "已设置 {node_name} 的计费信息: {} {currency}",
+        format_cycle(cycle_months)
+    ))
+}
+
+/// `/rate USD 7.2`, `/rate USD off` 或 `/rate base CNY`
+pub async fn rate(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = "用法:\n/rate - 查看汇率\n/rate base CNY - 设置基准货币\n/rate USD 7.2 - 设置 1 USD 兑换基准货币的数量\n/rate USD off - 删除汇率";
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let Some(currency) = args.first() else {
+        let base = base_currency(telegram_id).await?;
+        let rates = query_exchange_rates(db_pool, telegram_id).await?;
+
+        let mut message = format!("基准货币: {base}\n");
+        for (currency, rate) in rates {
+            let _ = writeln!(message, "1 {currency} = {rate} {base}");
+        }
+        return Ok(message);
+    };
+
+    let value = args.get(1).ok_or(usage)?;
+
+    if currency == "base" {
+        let base = value.to_uppercase();
+        upsert_base_currency(db_pool, telegram_id, &base).await?;
+        return Ok(format!("已设置基准货币为 {base}"));
+    }
+
+    let currency = currency.to_uppercase();
+
+    if value == "off" {
+        return match delete_exchange_rate(db_pool, telegram_id, &currency).await? {
+            0 => Err(format!("未设置 {currency} 的汇率")),
+            _ => Ok(format!("已删除 {currency} 的汇率")),
+        };
+    }
+
+    let rate = value
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| rate.is_finite() && *rate > 0.0)
+        .ok_or(usage)?;
+    upsert_exchange_rate(db_pool, telegram_id, &currency, rate).await?;
+
+    Ok(format!(
+        "已设置汇率: 1 {currency} = {rate} {}",
+        base_currency(telegram_id).await?
+    ))
+}
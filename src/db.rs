@@ -18,6 +18,71 @@ pub struct Monitor {
     pub notification_token: Option<String>,
 }
 
+#[derive(Debug, FromRow, Clone)]
+pub struct Mute {
+    pub node_uuid: Option<String>,
+    pub node_name: Option<String>,
+    pub until_at: i64,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct MaintenanceWindow {
+    pub id: i64,
+    pub telegram_id: i64,
+    pub node_uuid: Option<String>,
+    pub node_name: Option<String>,
+    pub weekdays: i64,
+    pub start_minute: i64,
+    pub end_minute: i64,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct SuppressedNotification {
+    pub id: i64,
+    pub telegram_id: i64,
+    pub chat_id: String,
+    pub node_uuid: Option<String>,
+    pub title: String,
+    pub message: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct Digest {
+    pub telegram_id: i64,
+    pub chat_id: i64,
+    pub frequency: String,
+    pub weekday: Option<i64>,
+    pub minute: i64,
+    pub next_run_at: i64,
+    pub last_sent_at: Option<i64>,
+    pub traffic_snapshot: Option<String>,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct TrafficQuota {
+    pub telegram_id: i64,
+    pub node_uuid: String,
+    pub node_name: String,
+    pub limit_bytes: i64,
+    pub reset_day: i64,
+    pub direction: String,
+    pub cycle_start: i64,
+    pub used_up: i64,
+    pub used_down: i64,
+    pub last_raw_up: Option<i64>,
+    pub last_raw_down: Option<i64>,
+    pub alerted_percent: i64,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct NodeBilling {
+    pub node_uuid: String,
+    pub node_name: String,
+    pub cycle_months: i64,
+    pub currency: String,
+}
+
 pub async fn connect_db(sqlite_db_file: &str) -> Result<&Pool<Sqlite>, ErrorString> {
     DB_POOL
         .get_or_try_init(|| async {
@@ -32,29 +97,145 @@ pub async fn connect_db(sqlite_db_file: &str) -> Result<&Pool<Sqlite>, ErrorStri
         .map_err(|e| ErrorString::from(e.to_string()))
 }
 
+/// 建表语句, 启动时依次执行
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS monitor (
+         id INTEGER PRIMARY KEY,
+         telegram_id INTEGER NOT NULL UNIQUE,
+         monitor_http_url TEXT NOT NULL,
+         monitor_ws_url TEXT,
+         total_server_count INTEGER NOT NULL,
+         site_name TEXT NOT NULL,
+         site_description TEXT NOT NULL,
+         komari_version TEXT NOT NULL,
+         notification_token TEXT
+     )",
+    "CREATE TABLE IF NOT EXISTS chat_permission (
+         chat_id INTEGER NOT NULL,
+         command TEXT NOT NULL,
+         level TEXT NOT NULL,
+         PRIMARY KEY (chat_id, command)
+     )",
+    "CREATE TABLE IF NOT EXISTS mute (
+         id INTEGER PRIMARY KEY,
+         telegram_id INTEGER NOT NULL,
+         node_uuid TEXT,
+         node_name TEXT,
+         until_at INTEGER NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS maintenance_window (
+         id INTEGER PRIMARY KEY,
+         telegram_id INTEGER NOT NULL,
+         node_uuid TEXT,
+         node_name TEXT,
+         weekdays INTEGER NOT NULL,
+         start_minute INTEGER NOT NULL,
+         end_minute INTEGER NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS suppressed_notification (
+         id INTEGER PRIMARY KEY,
+         telegram_id INTEGER NOT NULL,
+         chat_id TEXT NOT NULL,
+         node_uuid TEXT,
+         title TEXT NOT NULL,
+         message TEXT NOT NULL,
+         created_at INTEGER NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS digest (
+         telegram_id INTEGER NOT NULL,
+         chat_id INTEGER NOT NULL,
+         frequency TEXT NOT NULL,
+         weekday INTEGER,
+         minute INTEGER NOT NULL,
+         next_run_at INTEGER NOT NULL,
+         last_sent_at INTEGER,
+         traffic_snapshot TEXT,
+         PRIMARY KEY (telegram_id, chat_id)
+     )",
+    "CREATE TABLE IF NOT EXISTS traffic_quota (
+         telegram_id INTEGER NOT NULL,
+         node_uuid TEXT NOT NULL,
+         node_name TEXT NOT NULL,
+         limit_bytes INTEGER NOT NULL,
+         reset_day INTEGER NOT NULL,
+         direction TEXT NOT NULL,
+         cycle_start INTEGER NOT NULL,
+         used_up INTEGER NOT NULL,
+         used_down INTEGER NOT NULL,
+         last_raw_up INTEGER,
+         last_raw_down INTEGER,
+         alerted_percent INTEGER NOT NULL,
+         PRIMARY KEY (telegram_id, node_uuid)
+     )",
+    "CREATE TABLE IF NOT EXISTS expiry_setting (
+         telegram_id INTEGER PRIMARY KEY,
+         lead_days TEXT NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS expiry_reminder (
+         telegram_id INTEGER NOT NULL,
+         node_uuid TEXT NOT NULL,
+         expired_at TEXT NOT NULL,
+         lead_day INTEGER NOT NULL,
+         PRIMARY KEY (telegram_id, node_uuid, expired_at, lead_day)
+     )",
+    "CREATE TABLE IF NOT EXISTS node_billing (
+         telegram_id INTEGER NOT NULL,
+         node_uuid TEXT NOT NULL,
+         node_name TEXT NOT NULL,
+         cycle_months INTEGER NOT NULL,
+         currency TEXT NOT NULL,
+         PRIMARY KEY (telegram_id, node_uuid)
+     )",
+    "CREATE TABLE IF NOT EXISTS exchange_rate (
+         telegram_id INTEGER NOT NULL,
+         currency TEXT NOT NULL,
+         rate REAL NOT NULL,
+         PRIMARY KEY (telegram_id, currency)
+     )",
+    "CREATE TABLE IF NOT EXISTS cost_setting (
+         telegram_id INTEGER PRIMARY KEY,
+         base_currency TEXT NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS digest_export (
+         telegram_id INTEGER NOT NULL,
+         chat_id INTEGER NOT NULL,
+         format TEXT NOT NULL,
+         PRIMARY KEY (telegram_id, chat_id)
+     )",
+    "CREATE TABLE IF NOT EXISTS card_template (
+         telegram_id INTEGER NOT NULL,
+         kind TEXT NOT NULL,
+         template TEXT NOT NULL,
+         PRIMARY KEY (telegram_id, kind)
+     )",
+    "CREATE TABLE IF NOT EXISTS unit_setting (
+         telegram_id INTEGER PRIMARY KEY,
+         size_base TEXT NOT NULL,
+         rate_unit TEXT NOT NULL,
+         precision INTEGER NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS latency_alert (
+         telegram_id INTEGER PRIMARY KEY,
+         latency_ms REAL NOT NULL,
+         loss_percent REAL NOT NULL
+     )",
+    "CREATE TABLE IF NOT EXISTS latency_alert_state (
+         telegram_id INTEGER NOT NULL,
+         node_uuid TEXT NOT NULL,
+         task_id INTEGER NOT NULL,
+         PRIMARY KEY (telegram_id, node_uuid, task_id)
+     )",
+];
+
 pub async fn create_table(pool: &Pool<Sqlite>) -> Result<(), ErrorString> {
     // 创建表（如果不存在）
-    if sqlx::query(
-        "CREATE TABLE IF NOT EXISTS monitor (
-             id INTEGER PRIMARY KEY,
-             telegram_id INTEGER NOT NULL UNIQUE,
-             monitor_http_url TEXT NOT NULL,
-             monitor_ws_url TEXT,
-             total_server_count INTEGER NOT NULL,
-             site_name TEXT NOT NULL,
-             site_description TEXT NOT NULL,
-             komari_version TEXT NOT NULL,
-             notification_token TEXT
-         )",
-    )
-    .execute(pool)
-    .await
-    .is_ok()
-    {
-        Ok(())
-    } else {
-        Err(String::from("数据库错误"))
+    for statement in SCHEMA {
+        if sqlx::query(statement).execute(pool).await.is_err() {
+            return Err(String::from("数据库错误"));
+        }
     }
+
+    Ok(())
 }
 
 pub async fn query_monitor_by_telegram_id(
@@ -136,3 +317,824 @@ pub async fn update_notification_token(
         Err(e) => Err(format!("更新 notification_token 失败: {e}")),
     }
 }
+
+pub async fn query_chat_permissions(
+    pool: &Pool<Sqlite>,
+    chat_id: i64,
+) -> Result<Vec<(String, String)>, ErrorString> {
+    sqlx::query_as::<_, (String, String)>(
+        "SELECT command, level FROM chat_permission WHERE chat_id = ? ORDER BY command",
+    )
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询权限设置失败: {e}"))
+}
+
+pub async fn query_chat_permission(
+    pool: &Pool<Sqlite>,
+    chat_id: i64,
+    command: &str,
+) -> Result<Option<String>, ErrorString> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT level FROM chat_permission WHERE chat_id = ? AND command = ?",
+    )
+    .bind(chat_id)
+    .bind(command)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("查询权限设置失败: {e}"))
+}
+
+pub async fn upsert_chat_permission(
+    pool: &Pool<Sqlite>,
+    chat_id: i64,
+    command: &str,
+    level: &str,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO chat_permission (chat_id, command, level) VALUES (?, ?, ?)
+         ON CONFLICT (chat_id, command) DO UPDATE SET level = excluded.level",
+    )
+    .bind(chat_id)
+    .bind(command)
+    .bind(level)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("更新权限设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_chat_permission(
+    pool: &Pool<Sqlite>,
+    chat_id: i64,
+    command: &str,
+) -> Result<(), ErrorString> {
+    sqlx::query("DELETE FROM chat_permission WHERE chat_id = ? AND command = ?")
+        .bind(chat_id)
+        .bind(command)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("删除权限设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn insert_mute(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node: Option<(String, String)>,
+    until_at: i64,
+) -> Result<(), ErrorString> {
+    let (node_uuid, node_name) = node.unzip();
+
+    sqlx::query("DELETE FROM mute WHERE telegram_id = ? AND node_uuid IS ?")
+        .bind(telegram_id)
+        .bind(&node_uuid)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("更新静音设置失败: {e}"))?;
+
+    sqlx::query(
+        "INSERT INTO mute (telegram_id, node_uuid, node_name, until_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(telegram_id)
+    .bind(node_uuid)
+    .bind(node_name)
+    .bind(until_at)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("更新静音设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_mute(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node_uuid: Option<String>,
+) -> Result<u64, ErrorString> {
+    let result = match node_uuid {
+        Some(node_uuid) => {
+            sqlx::query("DELETE FROM mute WHERE telegram_id = ? AND node_uuid = ?")
+                .bind(telegram_id)
+                .bind(node_uuid)
+                .execute(pool)
+                .await
+        }
+        None => {
+            sqlx::query("DELETE FROM mute WHERE telegram_id = ?")
+                .bind(telegram_id)
+                .execute(pool)
+                .await
+        }
+    };
+
+    result
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除静音设置失败: {e}"))
+}
+
+pub async fn query_active_mutes(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    now: i64,
+) -> Result<Vec<Mute>, ErrorString> {
+    sqlx::query_as::<_, Mute>(
+        "SELECT node_uuid, node_name, until_at FROM mute
+         WHERE telegram_id = ? AND until_at > ?
+         ORDER BY until_at",
+    )
+    .bind(telegram_id)
+    .bind(now)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询静音设置失败: {e}"))
+}
+
+pub async fn delete_expired_mutes(pool: &Pool<Sqlite>, now: i64) -> Result<(), ErrorString> {
+    sqlx::query("DELETE FROM mute WHERE until_at <= ?")
+        .bind(now)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("清理静音设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn insert_maintenance_window(
+    pool: &Pool<Sqlite>,
+    window: MaintenanceWindow,
+) -> Result<i64, ErrorString> {
+    sqlx::query(
+        "INSERT INTO maintenance_window (telegram_id, node_uuid, node_name, weekdays, start_minute, end_minute)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(window.telegram_id)
+    .bind(window.node_uuid)
+    .bind(window.node_name)
+    .bind(window.weekdays)
+    .bind(window.start_minute)
+    .bind(window.end_minute)
+    .execute(pool)
+    .await
+    .map(|result| result.last_insert_rowid())
+    .map_err(|e| format!("保存维护窗口失败: {e}"))
+}
+
+pub async fn delete_maintenance_window(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    id: i64,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM maintenance_window WHERE telegram_id = ? AND id = ?")
+        .bind(telegram_id)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除维护窗口失败: {e}"))
+}
+
+pub async fn query_maintenance_windows(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<Vec<MaintenanceWindow>, ErrorString> {
+    sqlx::query_as::<_, MaintenanceWindow>(
+        "SELECT id, telegram_id, node_uuid, node_name, weekdays, start_minute, end_minute
+         FROM maintenance_window
+         WHERE telegram_id = ?
+         ORDER BY id",
+    )
+    .bind(telegram_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询维护窗口失败: {e}"))
+}
+
+pub async fn insert_suppressed_notification(
+    pool: &Pool<Sqlite>,
+    notification: SuppressedNotification,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO suppressed_notification (telegram_id, chat_id, node_uuid, title, message, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(notification.telegram_id)
+    .bind(notification.chat_id)
+    .bind(notification.node_uuid)
+    .bind(notification.title)
+    .bind(notification.message)
+    .bind(notification.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存被屏蔽的通知失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn query_suppressed_notifications(
+    pool: &Pool<Sqlite>,
+) -> Result<Vec<SuppressedNotification>, ErrorString> {
+    sqlx::query_as::<_, SuppressedNotification>(
+        "SELECT id, telegram_id, chat_id, node_uuid, title, message, created_at
+         FROM suppressed_notification
+         ORDER BY created_at",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询被屏蔽的通知失败: {e}"))
+}
+
+pub async fn delete_suppressed_notification(
+    pool: &Pool<Sqlite>,
+    id: i64,
+) -> Result<(), ErrorString> {
+    sqlx::query("DELETE FROM suppressed_notification WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("删除被屏蔽的通知失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn upsert_digest(pool: &Pool<Sqlite>, digest: Digest) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO digest (telegram_id, chat_id, frequency, weekday, minute, next_run_at, last_sent_at, traffic_snapshot)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (telegram_id, chat_id) DO UPDATE SET
+             frequency = excluded.frequency,
+             weekday = excluded.weekday,
+             minute = excluded.minute,
+             next_run_at = excluded.next_run_at,
+             last_sent_at = excluded.last_sent_at,
+             traffic_snapshot = excluded.traffic_snapshot",
+    )
+    .bind(digest.telegram_id)
+    .bind(digest.chat_id)
+    .bind(digest.frequency)
+    .bind(digest.weekday)
+    .bind(digest.minute)
+    .bind(digest.next_run_at)
+    .bind(digest.last_sent_at)
+    .bind(digest.traffic_snapshot)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存定时报告失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_digest(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    chat_id: i64,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM digest WHERE telegram_id = ? AND chat_id = ?")
+        .bind(telegram_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除定时报告失败: {e}"))
+}
+
+pub async fn query_digest(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    chat_id: i64,
+) -> Result<Option<Digest>, ErrorString> {
+    sqlx::query_as::<_, Digest>(
+        "SELECT telegram_id, chat_id, frequency, weekday, minute, next_run_at, last_sent_at, traffic_snapshot
+         FROM digest
+         WHERE telegram_id = ? AND chat_id = ?",
+    )
+    .bind(telegram_id)
+    .bind(chat_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("查询定时报告失败: {e}"))
+}
+
+pub async fn query_due_digests(pool: &Pool<Sqlite>, now: i64) -> Result<Vec<Digest>, ErrorString> {
+    sqlx::query_as::<_, Digest>(
+        "SELECT telegram_id, chat_id, frequency, weekday, minute, next_run_at, last_sent_at, traffic_snapshot
+         FROM digest
+         WHERE next_run_at <= ?",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询定时报告失败: {e}"))
+}
+
+pub async fn upsert_traffic_quota(
+    pool: &Pool<Sqlite>,
+    quota: TrafficQuota,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO traffic_quota (telegram_id, node_uuid, node_name, limit_bytes, reset_day, direction, cycle_start, used_up, used_down, last_raw_up, last_raw_down, alerted_percent)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (telegram_id, node_uuid) DO UPDATE SET
+             node_name = excluded.node_name,
+             limit_bytes = excluded.limit_bytes,
+             reset_day = excluded.reset_day,
+             direction = excluded.direction,
+             cycle_start = excluded.cycle_start,
+             used_up = excluded.used_up,
+             used_down = excluded.used_down,
+             last_raw_up = excluded.last_raw_up,
+             last_raw_down = excluded.last_raw_down,
+             alerted_percent = excluded.alerted_percent",
+    )
+    .bind(quota.telegram_id)
+    .bind(quota.node_uuid)
+    .bind(quota.node_name)
+    .bind(quota.limit_bytes)
+    .bind(quota.reset_day)
+    .bind(quota.direction)
+    .bind(quota.cycle_start)
+    .bind(quota.used_up)
+    .bind(quota.used_down)
+    .bind(quota.last_raw_up)
+    .bind(quota.last_raw_down)
+    .bind(quota.alerted_percent)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存流量配额失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_traffic_quota(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node_uuid: &str,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM traffic_quota WHERE telegram_id = ? AND node_uuid = ?")
+        .bind(telegram_id)
+        .bind(node_uuid)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除流量配额失败: {e}"))
+}
+
+pub async fn query_traffic_quotas(
+    pool: &Pool<Sqlite>,
+    telegram_id: Option<i64>,
+) -> Result<Vec<TrafficQuota>, ErrorString> {
+    sqlx::query_as::<_, TrafficQuota>(
+        "SELECT telegram_id, node_uuid, node_name, limit_bytes, reset_day, direction, cycle_start, used_up, used_down, last_raw_up, last_raw_down, alerted_percent
+         FROM traffic_quota
+         WHERE ? IS NULL OR telegram_id = ?
+         ORDER BY telegram_id, node_name",
+    )
+    .bind(telegram_id)
+    .bind(telegram_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询流量配额失败: {e}"))
+}
+
+pub async fn upsert_expiry_setting(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    lead_days: &str,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO expiry_setting (telegram_id, lead_days) VALUES (?, ?)
+         ON CONFLICT (telegram_id) DO UPDATE SET lead_days = excluded.lead_days",
+    )
+    .bind(telegram_id)
+    .bind(lead_days)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存到期提醒设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_expiry_setting(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM expiry_setting WHERE telegram_id = ?")
+        .bind(telegram_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除到期提醒设置失败: {e}"))
+}
+
+pub async fn query_expiry_settings(
+    pool: &Pool<Sqlite>,
+    telegram_id: Option<i64>,
+) -> Result<Vec<(i64, String)>, ErrorString> {
+    sqlx::query_as::<_, (i64, String)>(
+        "SELECT telegram_id, lead_days FROM expiry_setting WHERE ? IS NULL OR telegram_id = ?",
+    )
+    .bind(telegram_id)
+    .bind(telegram_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询到期提醒设置失败: {e}"))
+}
+
+/// 记录已发送的提醒, 返回该提醒此前是否未发送过
+pub async fn insert_expiry_reminder(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node_uuid: &str,
+    expired_at: &str,
+    lead_day: i64,
+) -> Result<bool, ErrorString> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO expiry_reminder (telegram_id, node_uuid, expired_at, lead_day)
+         VALUES (?, ?, ?, ?)",
+    )
+    .bind(telegram_id)
+    .bind(node_uuid)
+    .bind(expired_at)
+    .bind(lead_day)
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("保存到期提醒记录失败: {e}"))
+}
+
+pub async fn upsert_node_billing(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    billing: NodeBilling,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO node_billing (telegram_id, node_uuid, node_name, cycle_months, currency)
+         VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (telegram_id, node_uuid) DO UPDATE SET
+             node_name = excluded.node_name,
+             cycle_months = excluded.cycle_months,
+             currency = excluded.currency",
+    )
+    .bind(telegram_id)
+    .bind(billing.node_uuid)
+    .bind(billing.node_name)
+    .bind(billing.cycle_months)
+    .bind(billing.currency)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存计费设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_node_billing(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node_uuid: &str,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM node_billing WHERE telegram_id = ? AND node_uuid = ?")
+        .bind(telegram_id)
+        .bind(node_uuid)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除计费设置失败: {e}"))
+}
+
+pub async fn query_node_billings(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<Vec<NodeBilling>, ErrorString> {
+    sqlx::query_as::<_, NodeBilling>(
+        "SELECT node_uuid, node_name, cycle_months, currency FROM node_billing
+         WHERE telegram_id = ?
+         ORDER BY node_name",
+    )
+    .bind(telegram_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询计费设置失败: {e}"))
+}
+
+pub async fn upsert_exchange_rate(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    currency: &str,
+    rate: f64,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO exchange_rate (telegram_id, currency, rate) VALUES (?, ?, ?)
+         ON CONFLICT (telegram_id, currency) DO UPDATE SET rate = excluded.rate",
+    )
+    .bind(telegram_id)
+    .bind(currency)
+    .bind(rate)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存汇率失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_exchange_rate(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    currency: &str,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM exchange_rate WHERE telegram_id = ? AND currency = ?")
+        .bind(telegram_id)
+        .bind(currency)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除汇率失败: {e}"))
+}
+
+pub async fn query_exchange_rates(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<Vec<(String, f64)>, ErrorString> {
+    sqlx::query_as::<_, (String, f64)>(
+        "SELECT currency, rate FROM exchange_rate WHERE telegram_id = ? ORDER BY currency",
+    )
+    .bind(telegram_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询汇率失败: {e}"))
+}
+
+pub async fn upsert_base_currency(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    base_currency: &str,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO cost_setting (telegram_id, base_currency) VALUES (?, ?)
+         ON CONFLICT (telegram_id) DO UPDATE SET base_currency = excluded.base_currency",
+    )
+    .bind(telegram_id)
+    .bind(base_currency)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存基准货币失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn query_base_currency(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<Option<String>, ErrorString> {
+    sqlx::query_scalar::<_, String>("SELECT base_currency FROM cost_setting WHERE telegram_id = ?")
+        .bind(telegram_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("查询基准货币失败: {e}"))
+}
+
+pub async fn upsert_digest_export(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    chat_id: i64,
+    format: &str,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO digest_export (telegram_id, chat_id, format) VALUES (?, ?, ?)
+         ON CONFLICT (telegram_id, chat_id) DO UPDATE SET format = excluded.format",
+    )
+    .bind(telegram_id)
+    .bind(chat_id)
+    .bind(format)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存报告附件设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_digest_export(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    chat_id: i64,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM digest_export WHERE telegram_id = ? AND chat_id = ?")
+        .bind(telegram_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除报告附件设置失败: {e}"))
+}
+
+pub async fn query_digest_export(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    chat_id: i64,
+) -> Result<Option<String>, ErrorString> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT format FROM digest_export WHERE telegram_id = ? AND chat_id = ?",
+    )
+    .bind(telegram_id)
+    .bind(chat_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("查询报告附件设置失败: {e}"))
+}
+
+pub async fn upsert_card_template(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    kind: &str,
+    template: &str,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO card_template (telegram_id, kind, template) VALUES (?, ?, ?)
+         ON CONFLICT (telegram_id, kind) DO UPDATE SET template = excluded.template",
+    )
+    .bind(telegram_id)
+    .bind(kind)
+    .bind(template)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存模板失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_card_template(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    kind: &str,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM card_template WHERE telegram_id = ? AND kind = ?")
+        .bind(telegram_id)
+        .bind(kind)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除模板失败: {e}"))
+}
+
+pub async fn query_card_template(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    kind: &str,
+) -> Result<Option<String>, ErrorString> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT template FROM card_template WHERE telegram_id = ? AND kind = ?",
+    )
+    .bind(telegram_id)
+    .bind(kind)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("查询模板失败: {e}"))
+}
+
+pub async fn upsert_unit_setting(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    size_base: &str,
+    rate_unit: &str,
+    precision: i64,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO unit_setting (telegram_id, size_base, rate_unit, precision) VALUES (?, ?, ?, ?)
+         ON CONFLICT (telegram_id) DO UPDATE SET
+             size_base = excluded.size_base,
+             rate_unit = excluded.rate_unit,
+             precision = excluded.precision",
+    )
+    .bind(telegram_id)
+    .bind(size_base)
+    .bind(rate_unit)
+    .bind(precision)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存单位设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_unit_setting(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM unit_setting WHERE telegram_id = ?")
+        .bind(telegram_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除单位设置失败: {e}"))
+}
+
+/// (容量进制, 速率单位, 小数位数)
+pub async fn query_unit_setting(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<Option<(String, String, i64)>, ErrorString> {
+    sqlx::query_as::<_, (String, String, i64)>(
+        "SELECT size_base, rate_unit, precision FROM unit_setting WHERE telegram_id = ?",
+    )
+    .bind(telegram_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("查询单位设置失败: {e}"))
+}
+
+pub async fn upsert_latency_alert(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    latency_ms: f64,
+    loss_percent: f64,
+) -> Result<(), ErrorString> {
+    sqlx::query(
+        "INSERT INTO latency_alert (telegram_id, latency_ms, loss_percent) VALUES (?, ?, ?)
+         ON CONFLICT (telegram_id) DO UPDATE SET
+             latency_ms = excluded.latency_ms,
+             loss_percent = excluded.loss_percent",
+    )
+    .bind(telegram_id)
+    .bind(latency_ms)
+    .bind(loss_percent)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("保存延迟告警设置失败: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn delete_latency_alert(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+) -> Result<u64, ErrorString> {
+    sqlx::query("DELETE FROM latency_alert_state WHERE telegram_id = ?")
+        .bind(telegram_id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("删除延迟告警状态失败: {e}"))?;
+
+    sqlx::query("DELETE FROM latency_alert WHERE telegram_id = ?")
+        .bind(telegram_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| format!("删除延迟告警设置失败: {e}"))
+}
+
+/// (用户, 延迟阈值 ms, 丢包阈值 %)
+pub async fn query_latency_alerts(
+    pool: &Pool<Sqlite>,
+    telegram_id: Option<i64>,
+) -> Result<Vec<(i64, f64, f64)>, ErrorString> {
+    sqlx::query_as::<_, (i64, f64, f64)>(
+        "SELECT telegram_id, latency_ms, loss_percent FROM latency_alert
+         WHERE ? IS NULL OR telegram_id = ?",
+    )
+    .bind(telegram_id)
+    .bind(telegram_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("查询延迟告警设置失败: {e}"))
+}
+
+/// 记录正在告警的目标, 返回此前是否未在告警
+pub async fn insert_latency_alert_state(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node_uuid: &str,
+    task_id: i64,
+) -> Result<bool, ErrorString> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO latency_alert_state (telegram_id, node_uuid, task_id)
+         VALUES (?, ?, ?)",
+    )
+    .bind(telegram_id)
+    .bind(node_uuid)
+    .bind(task_id)
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("保存延迟告警状态失败: {e}"))
+}
+
+/// 清除已恢复目标的告警状态, 返回此前是否在告警
+pub async fn delete_latency_alert_state(
+    pool: &Pool<Sqlite>,
+    telegram_id: i64,
+    node_uuid: &str,
+    task_id: i64,
+) -> Result<bool, ErrorString> {
+    sqlx::query(
+        "DELETE FROM latency_alert_state WHERE telegram_id = ? AND node_uuid = ? AND task_id = ?",
+    )
+    .bind(telegram_id)
+    .bind(node_uuid)
+    .bind(task_id)
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("删除延迟告警状态失败: {e}"))
+}
@@ -0,0 +1,330 @@
+use crate::ErrorString;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::filter::NodeFilter;
+use crate::connection::ws_get::status::usage_percent;
+use crate::connection::ws_get::total_status::overview;
+use crate::db::{
+    DB_POOL, Digest, delete_digest, delete_digest_export, query_digest, query_digest_export,
+    query_due_digests, upsert_digest, upsert_digest_export,
+};
+use crate::expiry::expiring_nodes;
+use crate::export::{ExportFormat, export};
+use crate::markup::{LIST_LIMIT, Markup, parse_mode};
+use crate::schedule::{
+    format_clock, format_timestamp, next_clock, next_weekly, now, parse_clock, parse_weekday,
+    weekday_name,
+};
+use crate::units::load_units;
+use chrono::{DateTime, FixedOffset};
+use log::{error, info};
+use std::collections::HashMap;
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestLinkPreviewExt;
+use teloxide::types::InputFile;
+
+const BUSIEST_NODES: usize = 5;
+const EXPIRY_LOOKAHEAD_DAYS: i64 = 7;
+
+/// 各节点上次报告时的 (总上传, 总下载) 计数
+type TrafficSnapshot = HashMap<String, (u64, u64)>;
+
+fn next_run(digest: &Digest, from: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    match digest.weekday {
+        Some(weekday) => next_weekly(from, weekday, digest.minute),
+        None => next_clock(from, digest.minute),
+    }
+}
+
+fn describe(digest: &Digest) -> String {
+    match digest.weekday {
+        Some(weekday) => format!(
+            "每周 {} {}",
+            weekday_name(weekday),
+            format_clock(digest.minute)
+        ),
+        None => format!("每天 {}", format_clock(digest.minute)),
+    }
+}
+
+/// 生成定时报告正文与新的流量快照
+pub async fn build_digest(
+    telegram_id: i64,
+    last_snapshot: Option<&TrafficSnapshot>,
+) -> Result<(String, TrafficSnapshot), ErrorString> {
+    let filter = NodeFilter::default();
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes, units) =
+        tokio::try_join!(client.snapshot(), client.nodes(), load_units(telegram_id))?;
+    let overview = overview(
+        client.monitor(),
+        ws_data.clone(),
+        nodes.clone(),
+        telegram_id,
+        &filter,
+    )
+    .await?;
+
+    let node_name = |uuid: &str| {
+        nodes
+            .data
+            .iter()
+            .find(|node| node.uuid == uuid)
+            .map_or_else(|| uuid.to_string(), |node| node.name.clone())
+    };
+
+    let mut busiest: Vec<_> = ws_data.data.data.iter().collect();
+    busiest.sort_by(|a, b| b.1.cpu.usage.total_cmp(&a.1.cpu.usage));
+
+    let mut message = Markup::default();
+    message.line().line().text("繁忙节点:").line();
+    for (uuid, data) in busiest.iter().take(BUSIEST_NODES) {
+        message
+            .text(format!("{} - CPU ", node_name(uuid)))
+            .code(units.percent(data.cpu.usage))
+            .text(" RAM ")
+            .code(units.percent(usage_percent(data.ram.used, data.ram.total)))
+            .line();
+    }
+
+    let offline: Vec<_> = nodes
+        .data
+        .iter()
+        .filter(|node| !ws_data.data.online.contains(&node.uuid))
+        .map(|node| node.name.clone())
+        .collect();
+    message.line().field("离线节点", offline.len().to_string());
+    for name in offline.iter().take(LIST_LIMIT) {
+        message.text(name).line();
+    }
+    message.remaining(offline.len());
+
+    let snapshot: TrafficSnapshot = ws_data
+        .data
+        .data
+        .iter()
+        .map(|(uuid, data)| {
+            (
+                uuid.clone(),
+                (data.network.total_up, data.network.total_down),
+            )
+        })
+        .collect();
+
+    if let Some(last_snapshot) = last_snapshot {
+        // 计数器在节点重启后归零, 此时以当前值作为增量; 新节点没有基准, 从下一次报告开始统计
+        let (used_up, used_down) = snapshot.iter().fold((0, 0), |(up, down), (uuid, now)| {
+            let Some(last) = last_snapshot.get(uuid) else {
+                return (up, down);
+            };
+            let delta_up = now.0.checked_sub(last.0).unwrap_or(now.0);
+            let delta_down = now.1.checked_sub(last.1).unwrap_or(now.1);
+            (up + delta_up, down + delta_down)
+        });
+
+        message
+            .line()
+            .text("自上次报告以来流量: ")
+            .code(units.bytes(used_up))
+            .text(" 上传 / ")
+            .code(units.bytes(used_down))
+            .text(" 下载")
+            .line();
+    } else {
+        message.line().text("流量统计将从下一次报告开始").line();
+    }
+
+    let expiring = expiring_nodes(&nodes.data, now(), EXPIRY_LOOKAHEAD_DAYS);
+    if !expiring.is_empty() {
+        message
+            .line()
+            .text(format!("{EXPIRY_LOOKAHEAD_DAYS} 天内到期:"))
+            .line();
+        for (expired_at, node) in expiring.iter().take(LIST_LIMIT) {
+            message
+                .text(format!("{} - ", node.name))
+                .code(format_timestamp(expired_at.timestamp()))
+                .line();
+        }
+        message.remaining(expiring.len());
+    }
+
+    Ok((format!("{overview}{}", message.build()), snapshot))
+}
+
+async fn send_digest(bot: &Bot, digest: Digest) -> Result<(), ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    let now = now();
+
+    let last_snapshot = digest
+        .traffic_snapshot
+        .as_deref()
+        .and_then(|snapshot| serde_json::from_str::<TrafficSnapshot>(snapshot).ok());
+
+    // 无论成功与否都推进下一次运行时间, 避免失败时每分钟重试
+    let mut updated = Digest {
+        next_run_at: next_run(&digest, now).timestamp(),
+        ..digest.clone()
+    };
+
+    let result = match build_digest(digest.telegram_id, last_snapshot.as_ref()).await {
+        Ok((message, snapshot)) => {
+            let result = bot
+                .send_message(ChatId(digest.chat_id), message)
+                .parse_mode(parse_mode())
+                .disable_link_preview(true)
+                .await
+                .map_err(|e| format!("无法发送定时报告: {e}"));
+
+            if result.is_ok() {
+                updated.last_sent_at = Some(now.timestamp());
+                updated.traffic_snapshot = serde_json::to_string(&snapshot).ok();
+            }
+
+            result.map(|_| ())
+        }
+        Err(e) => Err(e),
+    };
+
+    upsert_digest(db_pool, updated).await?;
+
+    if result.is_ok()
+        && let Some(format) = query_digest_export(db_pool, digest.telegram_id, digest.chat_id)
+            .await?
+            .as_deref()
+            .and_then(ExportFormat::parse)
+    {
+        send_export(bot, &digest, format).await?;
+    }
+
+    result
+}
+
+/// 随定时报告附带的节点导出文件
+async fn send_export(bot: &Bot, digest: &Digest, format: ExportFormat) -> Result<(), ErrorString> {
+    let (content, file_name) = export(digest.telegram_id, format).await?;
+
+    bot.send_document(
+        ChatId(digest.chat_id),
+        InputFile::memory(content).file_name(file_name),
+    )
+    .await
+    .map_err(|e| format!("无法发送导出文件: {e}"))?;
+
+    Ok(())
+}
+
+pub async fn start_digest_job(bot: Bot) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_mins(1));
+
+    loop {
+        interval.tick().await;
+
+        let db_pool = DB_POOL
+            .get()
+            .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+        let digests = match query_due_digests(db_pool, now().timestamp()).await {
+            Ok(digests) => digests,
+            Err(e) => {
+                error!("定时报告: {e}");
+                continue;
+            }
+        };
+
+        for digest in digests {
+            let (telegram_id, chat_id) = (digest.telegram_id, digest.chat_id);
+
+            match send_digest(&bot, digest).await {
+                Ok(()) => info!("定时报告: 已向 {chat_id} 发送 {telegram_id} 的报告"),
+                Err(e) => error!("定时报告: 无法向 {chat_id} 发送 {telegram_id} 的报告: {e}"),
+            }
+        }
+    }
+}
+
+/// `/digest daily 09:00`, `/digest weekly mon 09:00` 或 `/digest off`
+pub async fn digest(
+    telegram_id: i64,
+    chat_id: i64,
+    args: &[String],
+) -> Result<String, ErrorString> {
+    let usage = "用法:\n/digest - 查看本聊天的定时报告\n/digest daily 09:00 [csv|json|xlsx]\n/digest weekly mon 09:00 [csv|json|xlsx]\n/digest off\n\n指定格式时随报告附带节点导出文件";
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let (weekday, clock, export_format) = match args.first().map(String::as_str) {
+        None => {
+            let Some(digest) = query_digest(db_pool, telegram_id, chat_id).await? else {
+                return Ok(String::from("本聊天未设置定时报告"));
+            };
+            let attachment = query_digest_export(db_pool, telegram_id, chat_id)
+                .await?
+                .map(|format| format!("\n附带导出文件: {format}"))
+                .unwrap_or_default();
+
+            return Ok(format!(
+                "本聊天的定时报告: {}\n下次发送: {}{attachment}",
+                describe(&digest),
+                format_timestamp(digest.next_run_at)
+            ));
+        }
+        Some("off") => {
+            delete_digest_export(db_pool, telegram_id, chat_id).await?;
+            return match delete_digest(db_pool, telegram_id, chat_id).await? {
+                0 => Err(String::from("本聊天未设置定时报告")),
+                _ => Ok(String::from("已关闭本聊天的定时报告")),
+            };
+        }
+        Some("daily") => (None, args.get(1).ok_or(usage)?, args.get(2)),
+        Some("weekly") => (
+            Some(parse_weekday(args.get(1).ok_or(usage)?).ok_or(usage)?),
+            args.get(2).ok_or(usage)?,
+            args.get(3),
+        ),
+        Some(_) => return Err(usage.to_string()),
+    };
+
+    let minute = parse_clock(clock).ok_or(usage)?;
+    let export_format = match export_format {
+        Some(format) => Some(ExportFormat::parse(format).ok_or(usage)?),
+        None => None,
+    };
+
+    let mut digest = Digest {
+        telegram_id,
+        chat_id,
+        frequency: String::from(if weekday.is_some() { "weekly" } else { "daily" }),
+        weekday,
+        minute,
+        next_run_at: 0,
+        last_sent_at: None,
+        traffic_snapshot: None,
+    };
+    digest.next_run_at = next_run(&digest, now()).timestamp();
+
+    // 保留流量快照, 修改时间不影响流量统计
+    if let Some(existing) = query_digest(db_pool, telegram_id, chat_id).await? {
+        digest.last_sent_at = existing.last_sent_at;
+        digest.traffic_snapshot = existing.traffic_snapshot;
+    }
+
+    upsert_digest(db_pool, digest.clone()).await?;
+
+    let attachment = if let Some(format) = export_format {
+        upsert_digest_export(db_pool, telegram_id, chat_id, format.extension()).await?;
+        format!("\n附带导出文件: {}", format.extension())
+    } else {
+        delete_digest_export(db_pool, telegram_id, chat_id).await?;
+        String::new()
+    };
+
+    Ok(format!(
+        "已设置定时报告: {}\n下次发送: {}{attachment}",
+        describe(&digest),
+        format_timestamp(digest.next_run_at)
+    ))
+}
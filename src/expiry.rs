@@ -0,0 +1,206 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::{ApiNodesData, get_api_nodes};
+use crate::db::{
+    DB_POOL, delete_expiry_setting, insert_expiry_reminder, query_expiry_settings,
+    upsert_expiry_setting,
+};
+use crate::markup::Markup;
+use crate::mute::deliver_alert;
+use crate::schedule::{format_timestamp, now, parse_komari_time};
+use chrono::{DateTime, FixedOffset};
+use log::error;
+use teloxide::prelude::*;
+
+const EXPIRING_DAYS: i64 = 30;
+const DEFAULT_LEAD_DAYS: &str = "7,3,1";
+
+/// 到期时间在 `within_days` 天内的节点, 按到期时间排序
+pub fn expiring_nodes(
+    nodes: &[ApiNodesData],
+    now: DateTime<FixedOffset>,
+    within_days: i64,
+) -> Vec<(DateTime<FixedOffset>, &ApiNodesData)> {
+    let mut expiring: Vec<_> = nodes
+        .iter()
+        .filter_map(|node| {
+            let expired_at = parse_komari_time(node.expired_at.as_deref()?)?;
+            (expired_at > now && (expired_at - now).num_days() < within_days)
+                .then_some((expired_at, node))
+        })
+        .collect();
+    expiring.sort_by_key(|(expired_at, _)| *expired_at);
+    expiring
+}
+
+/// 剩余天数, 不足一天按一天计
+pub fn days_left(expired_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> i64 {
+    ((expired_at - now).num_seconds() + 86399) / 86400
+}
+
+pub fn format_price(price: Option<f64>) -> String {
+    match price {
+        Some(price) if price > 0.0 => format!("{price:.2}"),
+        Some(price) if price < 0.0 => String::from("免费"),
+        _ => String::from("未设置"),
+    }
+}
+
+fn parse_lead_days(text: &str) -> Option<Vec<i64>> {
+    let mut lead_days = text
+        .split(',')
+        .map(|day| day.trim().parse::<i64>().ok().filter(|day| *day > 0))
+        .collect::<Option<Vec<_>>>()?;
+    lead_days.sort_unstable();
+    lead_days.dedup();
+    Some(lead_days)
+}
+
+async fn remind(bot: &Bot, telegram_id: i64, lead_days: &[i64]) -> Result<(), ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let Some(max_lead) = lead_days.iter().max() else {
+        return Ok(());
+    };
+
+    let nodes = get_api_nodes(telegram_id).await?;
+    let now = now();
+
+    for (expired_at, node) in expiring_nodes(&nodes.data, now, *max_lead + 1) {
+        let days_left = days_left(expired_at, now);
+        let expired_at_raw = node.expired_at.clone().unwrap_or_default();
+
+        // 同时跨过多个提前量时只提醒一次
+        let mut should_remind = false;
+        for lead_day in lead_days.iter().filter(|lead_day| days_left <= **lead_day) {
+            should_remind |= insert_expiry_reminder(
+                db_pool,
+                telegram_id,
+                &node.uuid,
+                &expired_at_raw,
+                *lead_day,
+            )
+            .await?;
+        }
+
+        if !should_remind {
+            continue;
+        }
+
+        let message = format!(
+            "{} 将于 {} 到期 (剩余 {days_left} 天)，价格: {}",
+            node.name,
+            format_timestamp(expired_at.timestamp()),
+            format_price(node.price)
+        );
+        deliver_alert(bot, telegram_id, Some(&node.uuid), "续费提醒", &message).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn start_expiry_job(bot: Bot) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_hours(1));
+
+    loop {
+        interval.tick().await;
+
+        let db_pool = DB_POOL
+            .get()
+            .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+        let settings = match query_expiry_settings(db_pool, None).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("续费提醒: {e}");
+                continue;
+            }
+        };
+
+        for (telegram_id, lead_days) in settings {
+            let lead_days = parse_lead_days(&lead_days).unwrap_or_default();
+
+            if let Err(e) = remind(&bot, telegram_id, &lead_days).await {
+                error!("续费提醒: 无法检查 {telegram_id}: {e}");
+            }
+        }
+    }
+}
+
+/// `/expiring` 的消息
+pub async fn expiring(telegram_id: i64) -> Result<String, ErrorString> {
+    let nodes = get_api_nodes(telegram_id).await?;
+    let now = now();
+
+    let expiring = expiring_nodes(&nodes.data, now, EXPIRING_DAYS);
+    if expiring.is_empty() {
+        return Ok(Markup::default()
+            .text(format!("{EXPIRING_DAYS} 天内没有即将到期的节点"))
+            .build());
+    }
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{EXPIRING_DAYS} 天内到期的节点:"))
+        .line()
+        .line();
+    for (expired_at, node) in expiring {
+        message
+            .text(&node.name)
+            .line()
+            .text("到期: ")
+            .code(format_timestamp(expired_at.timestamp()))
+            .text(" 剩余 ")
+            .code(days_left(expired_at, now).to_string())
+            .text(" 天")
+            .line()
+            .field("价格", format_price(node.price))
+            .line();
+    }
+
+    Ok(message.build())
+}
+
+/// `/expiry_remind 30,7,1` 或 `/expiry_remind off`
+pub async fn expiry_remind(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = format!(
+        "用法:\n/expiry_remind - 查看续费提醒设置\n/expiry_remind on - 使用默认提前天数 ({DEFAULT_LEAD_DAYS})\n/expiry_remind 30,7,1 - 设置提前提醒的天数\n/expiry_remind off - 关闭续费提醒"
+    );
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let lead_days = match args.first().map(String::as_str) {
+        None => {
+            return Ok(
+                match query_expiry_settings(db_pool, Some(telegram_id))
+                    .await?
+                    .first()
+                {
+                    Some((_, lead_days)) => format!("续费提醒已开启，提前 {lead_days} 天提醒"),
+                    None => format!("续费提醒未开启\n\n{usage}"),
+                },
+            );
+        }
+        Some("off") => {
+            return match delete_expiry_setting(db_pool, telegram_id).await? {
+                0 => Err(String::from("续费提醒未开启")),
+                _ => Ok(String::from("已关闭续费提醒")),
+            };
+        }
+        Some("on") => parse_lead_days(DEFAULT_LEAD_DAYS).unwrap_or_default(),
+        Some(lead_days) => parse_lead_days(lead_days).ok_or(usage)?,
+    };
+
+    let lead_days = lead_days
+        .iter()
+        .rev()
+        .map(std::string::ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    upsert_expiry_setting(db_pool, telegram_id, &lead_days).await?;
+
+    Ok(format!("已开启续费提醒，将在到期前 {lead_days} 天提醒"))
+}
@@ -0,0 +1,205 @@
+use crate::ErrorString;
+use crate::Reply;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::ws_get::status::sorted_node_uuids;
+use crate::schedule::now;
+use rust_xlsxwriter::Workbook;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "xlsx" | "excel" => Some(Self::Xlsx),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// 导出的一行, 字段顺序即表头顺序, 离线节点的实时数据为空
+#[derive(Debug, Serialize)]
+struct ExportRow {
+    id: i32,
+    name: String,
+    uuid: String,
+    online: bool,
+    region: String,
+    group: String,
+    tags: String,
+    cpu_name: String,
+    cpu_cores: i32,
+    arch: String,
+    virtualization: String,
+    os: String,
+    kernel_version: String,
+    gpu_name: String,
+    mem_total: u64,
+    swap_total: u64,
+    disk_total: u64,
+    price: Option<f64>,
+    expired_at: Option<String>,
+    cpu_usage: Option<f64>,
+    ram_used: Option<u64>,
+    swap_used: Option<u64>,
+    disk_used: Option<u64>,
+    load1: Option<f64>,
+    net_up: Option<u64>,
+    net_down: Option<u64>,
+    total_up: Option<u64>,
+    total_down: Option<u64>,
+    tcp: Option<u32>,
+    udp: Option<u32>,
+    uptime: Option<u64>,
+}
+
+async fn export_rows(telegram_id: i64) -> Result<Vec<ExportRow>, ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+
+    Ok(sorted_node_uuids(&ws_data, &nodes)
+        .into_iter()
+        .zip(1..)
+        .filter_map(|(uuid, id)| {
+            let node = nodes.data.iter().find(|node| node.uuid == uuid)?;
+            let online = ws_data.data.online.contains(&uuid);
+            let live = ws_data.data.data.get(&uuid).filter(|_| online);
+
+            Some(ExportRow {
+                id,
+                name: node.name.clone(),
+                uuid,
+                online,
+                region: node.region.clone(),
+                group: node.group_name(),
+                tags: node.tag_list().join(";"),
+                cpu_name: node.cpu_name.clone(),
+                cpu_cores: node.cpu_cores,
+                arch: node.arch.clone(),
+                virtualization: node.virtualization.clone(),
+                os: node.os.clone(),
+                kernel_version: node.kernel_version.clone(),
+                gpu_name: node.gpu_name.clone(),
+                mem_total: node.mem_total,
+                swap_total: node.swap_total,
+                disk_total: node.disk_total,
+                price: node.price,
+                expired_at: node.expired_at.clone(),
+                cpu_usage: live.map(|data| data.cpu.usage),
+                ram_used: live.map(|data| data.ram.used),
+                swap_used: live.map(|data| data.swap.used),
+                disk_used: live.map(|data| data.disk.used),
+                load1: live.map(|data| data.load.load1),
+                net_up: live.map(|data| data.network.up),
+                net_down: live.map(|data| data.network.down),
+                total_up: live.map(|data| data.network.total_up),
+                total_down: live.map(|data| data.network.total_down),
+                tcp: live.map(|data| data.connections.tcp),
+                udp: live.map(|data| data.connections.udp),
+                uptime: live.map(|data| data.uptime),
+            })
+        })
+        .collect())
+}
+
+fn to_csv(rows: &[ExportRow]) -> Result<Vec<u8>, ErrorString> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        writer
+            .serialize(row)
+            .map_err(|e| format!("无法生成 CSV: {e}"))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| format!("无法生成 CSV: {e}"))
+}
+
+fn to_xlsx(rows: &[ExportRow]) -> Result<Vec<u8>, ErrorString> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+
+    for (row_index, row) in (1u32..).zip(rows) {
+        let serde_json::Value::Object(fields) =
+            serde_json::to_value(row).map_err(|e| format!("无法生成 XLSX: {e}"))?
+        else {
+            continue;
+        };
+
+        // 开启 preserve_order 后字段按结构体顺序排列, 首行同时写入表头
+        for (column, (header, value)) in (0u16..).zip(&fields) {
+            if row_index == 1 {
+                worksheet
+                    .write_string(0, column, header)
+                    .map_err(|e| format!("无法生成 XLSX: {e}"))?;
+            }
+
+            let result = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::Number(number) => {
+                    worksheet.write_number(row_index, column, number.as_f64().unwrap_or_default())
+                }
+                serde_json::Value::Bool(bool) => worksheet.write_boolean(row_index, column, *bool),
+                serde_json::Value::String(string) => {
+                    worksheet.write_string(row_index, column, string)
+                }
+                value => worksheet.write_string(row_index, column, value.to_string()),
+            };
+            result.map_err(|e| format!("无法生成 XLSX: {e}"))?;
+        }
+    }
+
+    workbook
+        .save_to_buffer()
+        .map_err(|e| format!("无法生成 XLSX: {e}"))
+}
+
+/// `/export csv|json|xlsx`, 返回文件内容与文件名
+pub async fn export(
+    telegram_id: i64,
+    format: ExportFormat,
+) -> Result<(Vec<u8>, String), ErrorString> {
+    let rows = export_rows(telegram_id).await?;
+    if rows.is_empty() {
+        return Err(String::from("没有可导出的节点"));
+    }
+
+    let content = match format {
+        ExportFormat::Csv => to_csv(&rows)?,
+        ExportFormat::Json => {
+            serde_json::to_vec_pretty(&rows).map_err(|e| format!("无法生成 JSON: {e}"))?
+        }
+        ExportFormat::Xlsx => to_xlsx(&rows)?,
+    };
+
+    Ok((
+        content,
+        format!(
+            "komari-nodes-{}.{}",
+            now().format("%Y%m%d-%H%M"),
+            format.extension()
+        ),
+    ))
+}
+
+/// `/export csv|json|xlsx`
+pub async fn command(telegram_id: i64, format: Option<&str>) -> Reply {
+    match format.and_then(ExportFormat::parse) {
+        Some(format) => Reply::document(export(telegram_id, format).await, "无法导出"),
+        None => Reply::Text(String::from("用法: /export csv|json|xlsx")),
+    }
+}
@@ -0,0 +1,258 @@
+use crate::ErrorString;
+use crate::connection::api_records::{
+    ApiPingRecords, ApiRecord, get_api_ping_records, get_api_records, parse_hours,
+};
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::lookup_node;
+use crate::markup::Markup;
+use crate::units::{Units, load_units};
+
+const DEFAULT_HOURS: u32 = 6;
+
+/// 折线图的字符数, 记录更多时按时间分段取平均值
+const CHART_WIDTH: usize = 24;
+
+const CHART_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HistoryMetric {
+    Cpu,
+    Ram,
+    Load,
+    Net,
+    Conn,
+    Ping,
+}
+
+impl HistoryMetric {
+    const LOAD_METRICS: [Self; 5] = [Self::Cpu, Self::Ram, Self::Load, Self::Net, Self::Conn];
+
+    fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "cpu" => Some(Self::Cpu),
+            "ram" | "mem" => Some(Self::Ram),
+            "load" => Some(Self::Load),
+            "net" => Some(Self::Net),
+            "conn" => Some(Self::Conn),
+            "ping" => Some(Self::Ping),
+            _ => None,
+        }
+    }
+}
+
+/// 最小值, 平均值与最大值
+fn summary(values: &[f64]) -> (f64, f64, f64) {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let avg = values.iter().sum::<f64>() / values.len() as f64;
+    (min, avg, max)
+}
+
+/// 以方块字符绘制的折线, 高度按本段数据的最小值与最大值缩放
+fn sparkline(values: &[f64]) -> String {
+    let buckets = values.len().min(CHART_WIDTH);
+    let averages: Vec<f64> = (0..buckets)
+        .map(|bucket| {
+            let start = bucket * values.len() / buckets;
+            let end = (bucket + 1) * values.len() / buckets;
+            summary(&values[start..end]).1
+        })
+        .collect();
+
+    let (min, _, max) = summary(&averages);
+    let top = (CHART_LEVELS.len() - 1) as f64;
+
+    averages
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * top).round() as usize
+            } else {
+                0
+            };
+            CHART_LEVELS[level.min(CHART_LEVELS.len() - 1)]
+        })
+        .collect()
+}
+
+fn push_series(message: &mut Markup, label: &str, values: &[f64], format: impl Fn(f64) -> String) {
+    message.text(format!("{label}: "));
+    if values.is_empty() {
+        message.text("暂无记录").line();
+        return;
+    }
+
+    let (min, avg, max) = summary(values);
+    message
+        .code(sparkline(values))
+        .line()
+        .text("最小 ")
+        .code(format(min))
+        .text(" 平均 ")
+        .code(format(avg))
+        .text(" 最大 ")
+        .code(format(max))
+        .line();
+}
+
+fn push_load(message: &mut Markup, metric: HistoryMetric, records: &[ApiRecord], units: &Units) {
+    let series = |field: fn(&ApiRecord) -> f64| records.iter().map(field).collect::<Vec<_>>();
+
+    match metric {
+        HistoryMetric::Cpu => push_series(message, "CPU", &series(|record| record.cpu), |value| {
+            units.percent(value)
+        }),
+        HistoryMetric::Ram => {
+            push_series(
+                message,
+                "RAM",
+                &series(|record| record.ram as f64),
+                |value| units.bytes(value as u64),
+            );
+        }
+        HistoryMetric::Load => {
+            push_series(message, "LOAD", &series(|record| record.load), |value| {
+                units.number(value)
+            });
+        }
+        HistoryMetric::Net => {
+            push_series(
+                message,
+                "UP",
+                &series(|record| record.net_out as f64),
+                |value| units.rate(value),
+            );
+            push_series(
+                message,
+                "DOWN",
+                &series(|record| record.net_in as f64),
+                |value| units.rate(value),
+            );
+        }
+        HistoryMetric::Conn => push_series(
+            message,
+            "TCP",
+            &series(|record| record.connections as f64),
+            |value| format!("{value:.0}"),
+        ),
+        HistoryMetric::Ping => {}
+    }
+}
+
+/// 每个延迟任务一条折线, 丢包按超时记录所占比例计算
+fn push_ping(message: &mut Markup, ping: &ApiPingRecords, units: &Units) {
+    if ping.data.records.is_empty() {
+        message.text("PING: 暂无记录").line();
+        return;
+    }
+
+    let mut task_ids: Vec<i64> = ping.data.tasks.iter().map(|task| task.id).collect();
+    for record in &ping.data.records {
+        if !task_ids.contains(&record.task_id) {
+            task_ids.push(record.task_id);
+        }
+    }
+
+    for task_id in task_ids {
+        let records: Vec<f64> = ping
+            .data
+            .records
+            .iter()
+            .filter(|record| record.task_id == task_id)
+            .map(|record| record.value)
+            .collect();
+        if records.is_empty() {
+            continue;
+        }
+
+        let name = ping
+            .data
+            .tasks
+            .iter()
+            .find(|task| task.id == task_id)
+            .map_or_else(|| format!("任务 {task_id}"), |task| task.name.clone());
+        let latencies: Vec<f64> = records
+            .iter()
+            .copied()
+            .filter(|value| *value >= 0.0)
+            .collect();
+        let loss = (records.len() - latencies.len()) as f64 / records.len() as f64 * 100.0;
+
+        push_series(message, &format!("PING {name}"), &latencies, |value| {
+            format!("{} ms", units.number(value))
+        });
+        message.text("丢包 ").code(units.percent(loss)).line();
+    }
+}
+
+/// `/history NODE_ID [6h|3d] [cpu|ram|load|net|conn|ping]` 的消息
+pub async fn history(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = "用法: /history NODE_ID [时间范围] [cpu|ram|load|net|conn|ping]\n时间范围如 30m, 6h, 3d, 默认 6h";
+
+    let node = args.first().ok_or(usage)?;
+
+    let mut hours = DEFAULT_HOURS;
+    let mut metric = None;
+    for arg in &args[1..] {
+        match HistoryMetric::parse(&arg.to_lowercase()) {
+            Some(parsed) => metric = Some(parsed),
+            None => hours = parse_hours(arg)?,
+        }
+    }
+
+    let client = KomariClient::for_user(telegram_id).await?;
+    let (ws_data, nodes, units) =
+        tokio::try_join!(client.snapshot(), client.nodes(), load_units(telegram_id))?;
+    let node = lookup_node(&ws_data, &nodes, node)?;
+
+    let range = if hours.is_multiple_of(24) {
+        format!("{} 天", hours / 24)
+    } else {
+        format!("{hours} 小时")
+    };
+
+    let mut message = Markup::default();
+    message
+        .text(format!("{} | 最近 {range}", node.name))
+        .line()
+        .line();
+
+    let load_metrics: Vec<HistoryMetric> = match metric {
+        Some(HistoryMetric::Ping) => vec![],
+        Some(metric) => vec![metric],
+        None => HistoryMetric::LOAD_METRICS.to_vec(),
+    };
+
+    if !load_metrics.is_empty() {
+        let mut records = get_api_records(telegram_id, &node.uuid, hours)
+            .await?
+            .data
+            .records;
+        records.sort_by(|a, b| a.time.cmp(&b.time));
+
+        if records.is_empty() {
+            message.text("暂无负载记录").line();
+        } else {
+            for metric in load_metrics {
+                push_load(&mut message, metric, &records, &units);
+            }
+        }
+    }
+
+    if matches!(metric, None | Some(HistoryMetric::Ping)) {
+        match get_api_ping_records(telegram_id, &node.uuid, hours).await {
+            Ok(mut ping) => {
+                ping.data.records.sort_by(|a, b| a.time.cmp(&b.time));
+                message.line();
+                push_ping(&mut message, &ping, &units);
+            }
+            // 未配置延迟任务时只显示负载记录
+            Err(e) if metric.is_none() => {
+                message.line().text(format!("PING: {e}")).line();
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(message.build())
+}
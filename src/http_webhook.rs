@@ -1,6 +1,7 @@
 use crate::connection::create_reqwest_client;
 use crate::db::query_monitor_by_telegram_id;
-use crate::{ErrorString, Message, db};
+use crate::markup::Markup;
+use crate::{ErrorString, Message, db, mute};
 use axum::{
     Router,
     extract::{Path, State},
@@ -80,6 +81,15 @@ pub async fn http_callback(param1: String, param2: String, param3: String, body:
         return;
     };
 
+    match mute::suppress_if_muted(telegram_id, &param3, &title, &message).await {
+        Ok(true) => {
+            info!("Webhook: {telegram_id} 的通知处于静音中，已暂存");
+            return;
+        }
+        Ok(false) => {}
+        Err(e) => error!("Webhook: 无法检查静音状态: {e}"),
+    }
+
     let Ok(tg_token) = env::var("TG_TOKEN") else {
         error!("Webhook: 缺少TG_TOKEN环境变量");
         return;
@@ -136,6 +146,11 @@ pub async fn start_server(callback: CallbackFunc) {
 }
 
 pub async fn generate_notification_token(msg: Message) -> Result<String, ErrorString> {
+    // 令牌与回调地址是私密信息, 不能发到群组中
+    if !msg.chat.is_private() {
+        return Err(String::from("此命令只能用于私聊"));
+    }
+
     let telegram_id = if let Some(user) = msg.clone().from {
         user.id.0 as i64
     } else {
@@ -157,22 +172,55 @@ pub async fn generate_notification_token(msg: Message) -> Result<String, ErrorSt
     };
 
     let body = r#"{"message":"{{message}}", "title":"{{title}}"}"#;
-    Ok(format!(
-        r"已生成新的 Uuid:
-```
-{new_uuid}
-```
-请使用以下链接作为 Callback URL:
-```
-{callback_http_url}/telegrambot/{telegram_id}/{new_uuid}/CHAT_ID
-```
-以下内容作为 Callback Body:
-```
-{body}
-```
-
-最后选择 Method 为 `Post` 并保存
-
-请自行替换 CHAT\_ID，并确保该 Bot 可以访问到该聊天，CHAT\_ID 可从其他 Bot 获取"
-    ))
+    let mut message = Markup::default();
+    message
+        .text("已生成新的 Uuid:")
+        .line()
+        .pre(format!("{new_uuid}\n"))
+        .line()
+        .text("请使用以下链接作为 Callback URL:")
+        .line()
+        .pre(format!(
+            "{callback_http_url}/telegrambot/{telegram_id}/{new_uuid}/CHAT_ID\n"
+        ))
+        .line()
+        .text("以下内容作为 Callback Body:")
+        .line()
+        .pre(format!("{body}\n"))
+        .line()
+        .line()
+        .text("最后选择 Method 为 ")
+        .code("Post")
+        .text(" 并保存")
+        .line()
+        .line()
+        .text("请自行替换 CHAT_ID，并确保该 Bot 可以访问到该聊天，CHAT_ID 可从其他 Bot 获取");
+
+    Ok(message.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(chat: serde_json::Value) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": chat,
+            "from": { "id": 10, "is_bot": false, "first_name": "user" },
+            "text": "/generate_notification_token",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn group_invocation_is_refused() {
+        let group =
+            message(serde_json::json!({ "id": -100, "type": "supergroup", "title": "group" }));
+        assert_eq!(
+            generate_notification_token(group).await,
+            Err(String::from("此命令只能用于私聊"))
+        );
+    }
 }
@@ -0,0 +1,326 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::ApiNodesData;
+use crate::connection::api_records::ApiPingRecords;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::lookup_node;
+use crate::db::{
+    DB_POOL, delete_latency_alert, delete_latency_alert_state, insert_latency_alert_state,
+    query_latency_alerts, upsert_latency_alert,
+};
+use crate::markup::Markup;
+use crate::mute::deliver_alert;
+use crate::schedule::{now, parse_komari_time};
+use crate::units::{Units, load_units};
+use chrono::{DateTime, Duration, FixedOffset};
+use futures::stream::{self, StreamExt};
+use log::error;
+use teloxide::prelude::*;
+
+/// 统计最近多少分钟内的探测结果
+const WINDOW_MINUTES: i64 = 15;
+
+/// 汇总中列出的目标数量
+const WORST_COUNT: usize = 10;
+
+/// 同时请求延迟记录的节点数量
+const FETCH_CONCURRENCY: usize = 4;
+
+const DEFAULT_LATENCY_MS: f64 = 300.0;
+const DEFAULT_LOSS_PERCENT: f64 = 10.0;
+
+/// 某个节点到一个延迟任务目标的最近统计
+#[derive(Debug, Clone)]
+struct TaskStats {
+    task_id: i64,
+    name: String,
+    last: Option<f64>,
+    avg: Option<f64>,
+    loss: f64,
+    samples: usize,
+}
+
+impl TaskStats {
+    fn exceeds(&self, latency_ms: f64, loss_percent: f64) -> bool {
+        self.loss >= loss_percent || self.avg.is_some_and(|avg| avg >= latency_ms)
+    }
+
+    fn describe(&self, units: &Units) -> String {
+        format!(
+            "平均 {} 最近 {} 丢包 {}",
+            self.avg
+                .map_or_else(|| String::from("-"), |avg| format_ms(avg, units)),
+            self.last
+                .map_or_else(|| String::from("超时"), |last| format_ms(last, units)),
+            units.percent(self.loss)
+        )
+    }
+}
+
+fn format_ms(value: f64, units: &Units) -> String {
+    format!("{} ms", units.number(value))
+}
+
+/// 按任务统计 `since` 之后的探测结果, 超时记录计为丢包
+fn task_stats(ping: &ApiPingRecords, since: DateTime<FixedOffset>) -> Vec<TaskStats> {
+    let mut records: Vec<_> = ping
+        .data
+        .records
+        .iter()
+        .filter(|record| parse_komari_time(&record.time).is_none_or(|time| time >= since))
+        .collect();
+    records.sort_by(|a, b| a.time.cmp(&b.time));
+
+    let mut task_ids: Vec<i64> = ping.data.tasks.iter().map(|task| task.id).collect();
+    for record in &records {
+        if !task_ids.contains(&record.task_id) {
+            task_ids.push(record.task_id);
+        }
+    }
+
+    task_ids
+        .into_iter()
+        .filter_map(|task_id| {
+            let values: Vec<f64> = records
+                .iter()
+                .filter(|record| record.task_id == task_id)
+                .map(|record| record.value)
+                .collect();
+            if values.is_empty() {
+                return None;
+            }
+
+            let latencies: Vec<f64> = values
+                .iter()
+                .copied()
+                .filter(|value| *value >= 0.0)
+                .collect();
+            let name = ping
+                .data
+                .tasks
+                .iter()
+                .find(|task| task.id == task_id)
+                .map_or_else(|| format!("任务 {task_id}"), |task| task.name.clone());
+
+            Some(TaskStats {
+                task_id,
+                name,
+                last: values.last().copied().filter(|value| *value >= 0.0),
+                avg: (!latencies.is_empty())
+                    .then(|| latencies.iter().sum::<f64>() / latencies.len() as f64),
+                loss: (values.len() - latencies.len()) as f64 / values.len() as f64 * 100.0,
+                samples: values.len(),
+            })
+        })
+        .collect()
+}
+
+async fn node_stats(client: &impl KomariApi, uuid: &str) -> Result<Vec<TaskStats>, ErrorString> {
+    let ping = client.ping_records(uuid, 1).await?;
+    Ok(task_stats(&ping, now() - Duration::minutes(WINDOW_MINUTES)))
+}
+
+/// 所有节点的延迟统计, 无法获取的节点跳过
+async fn fleet_stats(telegram_id: i64) -> Result<Vec<(ApiNodesData, Vec<TaskStats>)>, ErrorString> {
+    let client = KomariClient::for_user(telegram_id).await?;
+    let nodes = client.nodes().await?;
+
+    let client = &client;
+
+    Ok(stream::iter(nodes.data)
+        .map(|node| async move {
+            let stats = node_stats(client, &node.uuid).await.ok()?;
+            (!stats.is_empty()).then_some((node, stats))
+        })
+        .buffer_unordered(FETCH_CONCURRENCY)
+        .filter_map(std::future::ready)
+        .collect()
+        .await)
+}
+
+/// `/latency [NODE]` 的消息, 未指定节点时列出全部节点中延迟最高的目标
+pub async fn latency(telegram_id: i64, node: Option<&str>) -> Result<String, ErrorString> {
+    let units = load_units(telegram_id).await?;
+    let mut message = Markup::default();
+
+    if let Some(node) = node {
+        let client = KomariClient::for_user(telegram_id).await?;
+        let (ws_data, nodes) = tokio::try_join!(client.snapshot(), client.nodes())?;
+        let node = lookup_node(&ws_data, &nodes, node)?;
+        let stats = node_stats(&client, &node.uuid).await?;
+
+        message
+            .text(format!("{} 延迟 (最近 {WINDOW_MINUTES} 分钟)", node.name))
+            .line()
+            .line();
+        if stats.is_empty() {
+            message.text("暂无延迟记录，请先在 Komari 中添加延迟监测任务");
+        }
+        for task in &stats {
+            message
+                .text(format!("{} ({} 次)", task.name, task.samples))
+                .line()
+                .code(task.describe(&units))
+                .line();
+        }
+
+        return Ok(message.build());
+    }
+
+    let fleet = fleet_stats(telegram_id).await?;
+    let mut worst: Vec<(&str, &TaskStats)> = fleet
+        .iter()
+        .flat_map(|(node, stats)| stats.iter().map(|task| (node.name.as_str(), task)))
+        .collect();
+
+    if worst.is_empty() {
+        return Err(String::from(
+            "暂无延迟记录，请先在 Komari 中添加延迟监测任务",
+        ));
+    }
+
+    // 全部丢包的目标排在最前
+    worst.sort_by(|a, b| {
+        let key = |task: &TaskStats| task.avg.unwrap_or(f64::INFINITY);
+        key(b.1)
+            .total_cmp(&key(a.1))
+            .then(b.1.loss.total_cmp(&a.1.loss))
+    });
+    worst.truncate(WORST_COUNT);
+
+    message
+        .text(format!("延迟最高的目标 (最近 {WINDOW_MINUTES} 分钟)"))
+        .line()
+        .line();
+    for (rank, (node, task)) in worst.iter().enumerate() {
+        message
+            .text(format!("{}. {node} → {}", rank + 1, task.name))
+            .line()
+            .code(task.describe(&units))
+            .line();
+    }
+
+    Ok(message.build())
+}
+
+/// 超过阈值时告警一次, 恢复后再发送恢复通知
+async fn check(
+    bot: &Bot,
+    telegram_id: i64,
+    latency_ms: f64,
+    loss_percent: f64,
+) -> Result<(), ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    let units = load_units(telegram_id).await?;
+
+    for (node, stats) in fleet_stats(telegram_id).await? {
+        for task in stats {
+            let (changed, title) = if task.exceeds(latency_ms, loss_percent) {
+                (
+                    insert_latency_alert_state(db_pool, telegram_id, &node.uuid, task.task_id)
+                        .await?,
+                    "延迟告警",
+                )
+            } else {
+                (
+                    delete_latency_alert_state(db_pool, telegram_id, &node.uuid, task.task_id)
+                        .await?,
+                    "延迟恢复",
+                )
+            };
+
+            if !changed {
+                continue;
+            }
+
+            let message = format!("{} → {}: {}", node.name, task.name, task.describe(&units));
+            deliver_alert(bot, telegram_id, Some(&node.uuid), title, &message).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn start_latency_job(bot: Bot) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_mins(5));
+
+    loop {
+        interval.tick().await;
+
+        let db_pool = DB_POOL
+            .get()
+            .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+        let settings = match query_latency_alerts(db_pool, None).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("延迟告警: {e}");
+                continue;
+            }
+        };
+
+        for (telegram_id, latency_ms, loss_percent) in settings {
+            if let Err(e) = check(&bot, telegram_id, latency_ms, loss_percent).await {
+                error!("延迟告警: 无法检查 {telegram_id}: {e}");
+            }
+        }
+    }
+}
+
+/// `/latency_alert [300ms] [10%]`, `/latency_alert on` 或 `/latency_alert off`
+pub async fn latency_alert(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = format!(
+        "用法:\n/latency_alert - 查看延迟告警设置\n/latency_alert on - 使用默认阈值 ({DEFAULT_LATENCY_MS} ms, {DEFAULT_LOSS_PERCENT}%)\n/latency_alert 300ms 10% - 设置平均延迟与丢包阈值\n/latency_alert off - 关闭延迟告警"
+    );
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let current = query_latency_alerts(db_pool, Some(telegram_id))
+        .await?
+        .first()
+        .map(|(_, latency_ms, loss_percent)| (*latency_ms, *loss_percent));
+
+    let (mut latency_ms, mut loss_percent) =
+        current.unwrap_or((DEFAULT_LATENCY_MS, DEFAULT_LOSS_PERCENT));
+
+    match args.first().map(String::as_str) {
+        None => {
+            return Ok(match current {
+                Some((latency_ms, loss_percent)) => format!(
+                    "延迟告警已开启: 最近 {WINDOW_MINUTES} 分钟平均延迟 ≥ {latency_ms} ms 或丢包 ≥ {loss_percent}%"
+                ),
+                None => format!("延迟告警未开启\n\n{usage}"),
+            });
+        }
+        Some("off") => {
+            return match delete_latency_alert(db_pool, telegram_id).await? {
+                0 => Err(String::from("延迟告警未开启")),
+                _ => Ok(String::from("已关闭延迟告警")),
+            };
+        }
+        Some("on") => {}
+        Some(_) => {
+            for arg in args {
+                let value =
+                    |text: &str| text.trim().parse::<f64>().ok().filter(|value| *value > 0.0);
+
+                if let Some(ms) = arg.strip_suffix("ms").and_then(value) {
+                    latency_ms = ms;
+                } else if let Some(percent) = arg.strip_suffix('%').and_then(value) {
+                    loss_percent = percent.min(100.0);
+                } else {
+                    return Err(usage);
+                }
+            }
+        }
+    }
+
+    upsert_latency_alert(db_pool, telegram_id, latency_ms, loss_percent).await?;
+
+    Ok(format!(
+        "已开启延迟告警: 最近 {WINDOW_MINUTES} 分钟平均延迟 ≥ {latency_ms} ms 或丢包 ≥ {loss_percent}%"
+    ))
+}
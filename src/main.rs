@@ -1,16 +1,42 @@
 #![warn(clippy::all, clippy::pedantic)]
 
+mod backup;
+mod callback;
 mod connection;
+mod cost;
 mod db;
+mod digest;
+mod expiry;
+mod export;
+mod history;
 mod http_webhook;
-
+mod latency;
+mod markup;
+#[cfg(test)]
+mod mock_komari;
+mod mute;
+mod permission;
+mod quota;
+mod schedule;
+mod template;
+mod units;
+
+use crate::callback::{CallbackAction, CallbackData};
+use crate::connection::filter::NodeFilter;
 use crate::connection::first_init_read;
+use crate::connection::query::NodeQuery;
+use crate::connection::ws_get::find::parse_ws_find;
 use crate::connection::ws_get::get_node_id::ws_get_node_id;
+use crate::connection::ws_get::groups::parse_ws_groups;
+use crate::connection::ws_get::offline::parse_ws_offline;
+use crate::connection::ws_get::regions::{parse_ws_region_nodes, parse_ws_regions};
 use crate::connection::ws_get::status::{
-    make_keyboard_for_single, parse_ws_single_server_by_index,
+    NodeTab, make_keyboard_for_single, parse_ws_single_server_by_index,
 };
-use crate::connection::ws_get::total_status::parse_ws_total_status;
+use crate::connection::ws_get::{compare, find, get_node_id, status, top, total_status};
 use crate::http_webhook::generate_notification_token;
+use crate::markup::{Markup, parse_mode};
+use crate::permission::check_permission;
 use db::{DB_POOL, Monitor, connect_db, create_table, delete_monitor, insert_monitor};
 use log::info;
 use reqwest::Url;
@@ -20,7 +46,7 @@ use std::{env, fs};
 use teloxide::prelude::*;
 use teloxide::sugar::bot::BotMessagesExt;
 use teloxide::sugar::request::RequestLinkPreviewExt;
-use teloxide::types::{ParseMode, ReplyParameters};
+use teloxide::types::{InlineKeyboardMarkup, InputFile, ReplyParameters};
 use teloxide::utils::command::parse_command;
 
 pub type ErrorString = String;
@@ -33,6 +59,34 @@ struct Config {
     callback_http_port: u16,
     callback_http_url: String,
     log_level: String,
+    #[serde(default = "default_timezone")]
+    timezone: String,
+    #[serde(default)]
+    admin_ids: Vec<i64>,
+    #[serde(default)]
+    callback_secret: String,
+    #[serde(default = "default_metadata_cache_secs")]
+    metadata_cache_secs: u64,
+    #[serde(default = "default_parse_mode")]
+    parse_mode: String,
+    #[serde(default = "default_request_timeout_secs")]
+    request_timeout_secs: u64,
+}
+
+fn default_timezone() -> String {
+    String::from("+00:00")
+}
+
+fn default_metadata_cache_secs() -> u64 {
+    300
+}
+
+fn default_parse_mode() -> String {
+    String::from("markdown")
+}
+
+fn default_request_timeout_secs() -> u64 {
+    5
 }
 
 #[tokio::main]
@@ -49,11 +103,46 @@ async fn main() {
     })
     .unwrap();
 
+    // komari-tgbot restore BACKUP_FILE
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("restore") {
+        let Some(backup_file) = args.get(2) else {
+            log::error!("用法: komari-tgbot restore BACKUP_FILE");
+            return;
+        };
+
+        match backup::restore(&config.db_file, backup_file).await {
+            Ok(message) => info!("{message}"),
+            Err(e) => log::error!("恢复数据库失败: {e}"),
+        }
+        return;
+    }
+
     unsafe {
         env::set_var("TG_TOKEN", config.telegram_token.clone());
         env::set_var("CALLBACK_HTTP_PORT", config.callback_http_port.to_string());
         env::set_var("CALLBACK_HTTP_URL", config.callback_http_url.clone());
-        env::set_var("BOT_NAME", config.bot_name.clone())
+        env::set_var("BOT_NAME", config.bot_name.clone());
+        env::set_var("TIMEZONE", config.timezone.clone());
+        env::set_var("CALLBACK_SECRET", config.callback_secret.clone());
+        env::set_var(
+            "METADATA_CACHE_SECS",
+            config.metadata_cache_secs.to_string(),
+        );
+        env::set_var("PARSE_MODE", config.parse_mode.to_lowercase());
+        env::set_var(
+            "REQUEST_TIMEOUT_SECS",
+            config.request_timeout_secs.to_string(),
+        );
+        env::set_var(
+            "ADMIN_IDS",
+            config
+                .admin_ids
+                .iter()
+                .map(std::string::ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        );
     };
 
     info!("Starting...");
@@ -73,6 +162,12 @@ async fn main() {
         },
     ));
 
+    tokio::spawn(mute::start_summary_job(bot.clone()));
+    tokio::spawn(digest::start_digest_job(bot.clone()));
+    tokio::spawn(quota::start_quota_job(bot.clone()));
+    tokio::spawn(expiry::start_expiry_job(bot.clone()));
+    tokio::spawn(latency::start_latency_job(bot.clone()));
+
     let handler = dptree::entry()
         .branch(
             Update::filter_message().endpoint(move |bot: Bot, msg: Message| async move {
@@ -81,7 +176,9 @@ async fn main() {
                     return Ok(());
                 };
 
-                let command = match parse(msg.text().unwrap_or(""), bot_name.as_str()) {
+                // 发送文件时命令位于说明文字中
+                let text = msg.text().or(msg.caption()).unwrap_or("");
+                let command = match parse(text, bot_name.as_str()) {
                     Ok(Some(cmd)) => {
                         info!("接收到来自 {:?} 命令: {:?}", msg.from, cmd);
                         cmd
@@ -107,13 +204,170 @@ async fn main() {
 enum Command {
     Start,
     Help,
-    Connect { http_url: String },
+    Connect {
+        http_url: String,
+    },
     Disconnect,
     Update,
-    GetNodeId,
-    TotalStatus,
-    Status { node_id: i32 },
+    GetNodeId {
+        filter: Vec<String>,
+    },
+    TotalStatus {
+        filter: Vec<String>,
+    },
+    Status {
+        node_id: Option<i32>,
+        group: Option<String>,
+    },
+    Groups,
+    Offline,
+    Regions,
+    Top {
+        metric: Option<String>,
+    },
+    Compare {
+        left: Option<String>,
+        right: Option<String>,
+    },
+    Find {
+        args: Vec<String>,
+    },
+    History {
+        args: Vec<String>,
+    },
+    Latency {
+        node: Option<String>,
+    },
+    LatencyAlert {
+        args: Vec<String>,
+    },
+    Export {
+        format: Option<String>,
+    },
+    Backup,
+    ExportSettings,
+    ImportSettings,
     GenerateNotificationToken,
+    Permission {
+        command: Option<String>,
+        level: Option<String>,
+    },
+    Mute {
+        args: Vec<String>,
+    },
+    Unmute {
+        target: Option<String>,
+    },
+    Maintenance {
+        args: Vec<String>,
+    },
+    Digest {
+        args: Vec<String>,
+    },
+    Quota {
+        args: Vec<String>,
+    },
+    Traffic,
+    Expiring,
+    ExpiryRemind {
+        args: Vec<String>,
+    },
+    Cost,
+    Billing {
+        args: Vec<String>,
+    },
+    Rate {
+        args: Vec<String>,
+    },
+    Template {
+        kind: Option<String>,
+        body: Option<String>,
+    },
+    Units {
+        args: Vec<String>,
+    },
+}
+
+impl Command {
+    const NAMES: &'static [&'static str] = &[
+        "start",
+        "help",
+        "connect",
+        "disconnect",
+        "update",
+        "get_node_id",
+        "total_status",
+        "status",
+        "generate_notification_token",
+        "permission",
+        "mute",
+        "unmute",
+        "maintenance",
+        "digest",
+        "quota",
+        "traffic",
+        "expiring",
+        "expiry_remind",
+        "cost",
+        "billing",
+        "rate",
+        "groups",
+        "offline",
+        "regions",
+        "top",
+        "compare",
+        "find",
+        "history",
+        "latency",
+        "latency_alert",
+        "export",
+        "backup",
+        "export_settings",
+        "import_settings",
+        "template",
+        "units",
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Start => "start",
+            Command::Help => "help",
+            Command::Connect { .. } => "connect",
+            Command::Disconnect => "disconnect",
+            Command::Update => "update",
+            Command::GetNodeId { .. } => "get_node_id",
+            Command::TotalStatus { .. } => "total_status",
+            Command::Status { .. } => "status",
+            Command::GenerateNotificationToken => "generate_notification_token",
+            Command::Permission { .. } => "permission",
+            Command::Mute { .. } => "mute",
+            Command::Unmute { .. } => "unmute",
+            Command::Maintenance { .. } => "maintenance",
+            Command::Digest { .. } => "digest",
+            Command::Quota { .. } => "quota",
+            Command::Traffic => "traffic",
+            Command::Expiring => "expiring",
+            Command::ExpiryRemind { .. } => "expiry_remind",
+            Command::Cost => "cost",
+            Command::Billing { .. } => "billing",
+            Command::Rate { .. } => "rate",
+            Command::Groups => "groups",
+            Command::Offline => "offline",
+            Command::Regions => "regions",
+            Command::Top { .. } => "top",
+            Command::Compare { .. } => "compare",
+            Command::Find { .. } => "find",
+            Command::History { .. } => "history",
+            Command::Latency { .. } => "latency",
+            Command::LatencyAlert { .. } => "latency_alert",
+            Command::Export { .. } => "export",
+            Command::Backup => "backup",
+            Command::ExportSettings => "export_settings",
+            Command::ImportSettings => "import_settings",
+            Command::Template { .. } => "template",
+            Command::Units { .. } => "units",
+        }
+    }
 }
 
 fn parse(text: &str, bot_name: &str) -> Result<Option<Command>, ErrorString> {
@@ -121,11 +375,13 @@ fn parse(text: &str, bot_name: &str) -> Result<Option<Command>, ErrorString> {
         return Ok(None);
     }
 
-    let (cmd, args) = match parse_command(text, bot_name).ok_or("无法解析的命令") {
-        Ok(cmd) => cmd,
-        Err(_) => return Ok(None),
+    let Some((cmd, args)) = parse_command(text, bot_name) else {
+        return Ok(None);
     };
 
+    let all = || args.iter().map(std::string::ToString::to_string).collect();
+    let nth = |index: usize| args.get(index).map(std::string::ToString::to_string);
+
     match cmd {
         "start" => Ok(Some(Command::Start)),
         "help" => Ok(Some(Command::Help)),
@@ -144,371 +400,514 @@ fn parse(text: &str, bot_name: &str) -> Result<Option<Command>, ErrorString> {
         }
         "disconnect" => Ok(Some(Command::Disconnect)),
         "update" => Ok(Some(Command::Update)),
-        "get_node_id" => Ok(Some(Command::GetNodeId)),
-        "total_status" => Ok(Some(Command::TotalStatus)),
+        "get_node_id" => Ok(Some(Command::GetNodeId { filter: all() })),
+        "total_status" => Ok(Some(Command::TotalStatus { filter: all() })),
         "status" => {
-            let node_id = args.first().unwrap_or(&"1").parse::<i32>().unwrap_or(1);
-            Ok(Some(Command::Status { node_id }))
+            let group = args
+                .iter()
+                .find_map(|arg| arg.strip_prefix("group="))
+                .map(std::string::ToString::to_string);
+            let node_id = args.iter().find_map(|arg| arg.parse::<i32>().ok());
+            Ok(Some(Command::Status { node_id, group }))
         }
+        "groups" => Ok(Some(Command::Groups)),
+        "offline" => Ok(Some(Command::Offline)),
+        "regions" => Ok(Some(Command::Regions)),
+        "top" => Ok(Some(Command::Top { metric: nth(0) })),
+        "compare" => Ok(Some(Command::Compare {
+            left: nth(0),
+            right: nth(1),
+        })),
+        "find" => Ok(Some(Command::Find { args: all() })),
+        "export" => Ok(Some(Command::Export { format: nth(0) })),
+        "backup" => Ok(Some(Command::Backup)),
+        "export_settings" => Ok(Some(Command::ExportSettings)),
+        "import_settings" => Ok(Some(Command::ImportSettings)),
         "generate_notification_token" => Ok(Some(Command::GenerateNotificationToken)),
+        "permission" => Ok(Some(Command::Permission {
+            command: nth(0),
+            level: nth(1),
+        })),
+        "mute" => Ok(Some(Command::Mute { args: all() })),
+        "unmute" => Ok(Some(Command::Unmute { target: nth(0) })),
+        "maintenance" => Ok(Some(Command::Maintenance { args: all() })),
+        "digest" => Ok(Some(Command::Digest { args: all() })),
+        "quota" => Ok(Some(Command::Quota { args: all() })),
+        "traffic" => Ok(Some(Command::Traffic)),
+        "expiring" => Ok(Some(Command::Expiring)),
+        "expiry_remind" => Ok(Some(Command::ExpiryRemind { args: all() })),
+        "cost" => Ok(Some(Command::Cost)),
+        "billing" => Ok(Some(Command::Billing { args: all() })),
+        "rate" => Ok(Some(Command::Rate { args: all() })),
+        "template" => {
+            // 模板可包含换行, 取类型之后的原始文本
+            let body = text
+                .trim_start()
+                .split_once(char::is_whitespace)
+                .and_then(|(_, rest)| rest.trim_start().split_once(char::is_whitespace))
+                .map(|(_, body)| body.trim().to_string())
+                .filter(|body| !body.is_empty());
+
+            Ok(Some(Command::Template { kind: nth(0), body }))
+        }
+        "history" => Ok(Some(Command::History { args: all() })),
+        "latency" => Ok(Some(Command::Latency { node: nth(0) })),
+        "latency_alert" => Ok(Some(Command::LatencyAlert { args: all() })),
+        "units" => Ok(Some(Command::Units { args: all() })),
         _ => Ok(None),
     }
 }
 
-async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
-    if msg.clone().from.map(|user| user.is_channel()).unwrap_or(true) {
-        return Ok(());
+/// 命令的回复
+pub enum Reply {
+    /// 纯文本
+    Text(String),
+    /// 以配置的解析模式发送, 可附带按钮
+    Formatted(String, Option<InlineKeyboardMarkup>),
+    /// 文件
+    Document(InputFile),
+}
+
+impl Reply {
+    /// 纯文本结果, 失败时在错误前加上说明
+    #[must_use]
+    pub fn text(result: Result<String, ErrorString>, context: &str) -> Self {
+        Self::Text(result.unwrap_or_else(|e| format!("{context}: {e}")))
     }
 
-    match cmd {
-        Command::Start => {
-            bot.send_message(
-                msg.chat.id,
-                r"欢迎使用 Komari Unofficial Telegram Bot
+    #[must_use]
+    pub fn formatted(result: Result<String, ErrorString>, context: &str) -> Self {
+        match result {
+            Ok(text) => Self::Formatted(text, None),
+            Err(e) => Self::Text(format!("{context}: {e}")),
+        }
+    }
 
-输入 /help 查看使用方法
+    #[must_use]
+    pub fn keyboard(
+        result: Result<(String, InlineKeyboardMarkup), ErrorString>,
+        context: &str,
+    ) -> Self {
+        match result {
+            Ok((text, keyboard)) => Self::Formatted(text, Some(keyboard)),
+            Err(e) => Self::Text(format!("{context}: {e}")),
+        }
+    }
 
-> 本 Bot 开源于 [Github](https://github.com/GenshinMinecraft/komari-tg-bot), 使用强力的 [Rust](https://www.rust-lang.org/) 驱动, 爱来自 [Komari](https://github.com/komari-monitor/komari)", )
-                .reply_parameters(ReplyParameters::new(msg.id))
-                .parse_mode(ParseMode::MarkdownV2)
-                .disable_link_preview(true)
-                .await?;
+    #[must_use]
+    pub fn document(result: Result<(Vec<u8>, String), ErrorString>, context: &str) -> Self {
+        match result {
+            Ok((bytes, file_name)) => Self::Document(InputFile::memory(bytes).file_name(file_name)),
+            Err(e) => Self::Text(format!("{context}: {e}")),
+        }
+    }
 
-            Ok(())
+    async fn send(self, bot: &Bot, msg: &Message) -> ResponseResult<()> {
+        let reply_parameters = ReplyParameters::new(msg.id);
+        match self {
+            Self::Text(text) => {
+                bot.send_message(msg.chat.id, text)
+                    .reply_parameters(reply_parameters)
+                    .await?;
+            }
+            Self::Formatted(text, keyboard) => {
+                let request = bot
+                    .send_message(msg.chat.id, text)
+                    .parse_mode(parse_mode())
+                    .reply_parameters(reply_parameters)
+                    .disable_link_preview(true);
+                match keyboard {
+                    Some(keyboard) => request.reply_markup(keyboard).await?,
+                    None => request.await?,
+                };
+            }
+            Self::Document(file) => {
+                bot.send_document(msg.chat.id, file)
+                    .reply_parameters(reply_parameters)
+                    .await?;
+            }
         }
-        Command::Help => {
-            bot.send_message(
-                msg.chat.id,
-                r"Komari Unofficial Telegram Bot
+        Ok(())
+    }
+}
+
+const HELP: &str = r"Komari Unofficial Telegram Bot
 /start, /help - 打印本菜单
 
 /connect HTTP_URL - 连接到 Komari 服务 (自动推断 WebSocket URL\)
 /disconnect - 断开已保存的连接
 /update - 更新已保存的连接 (增删服务器或疑难杂症可使用\)
 
-/get_node_id - 获取所有节点的 ID (仅本 Bot\)
-/total_status - 获取所有节点的运行状态
-/status NODE_ID - 获取指定节点的运行状态
+/get_node_id [group=分组] [tag=标签] - 分页列出节点，点击查看状态 (仅本 Bot\)
+/total_status [group=分组] [tag=标签] - 获取所有节点的运行状态
+/status NODE_ID [group=分组] - 获取指定节点的运行状态, 按钮在分组内翻页
+/groups - 查看各分组的在线情况
+/offline - 查看离线节点及离线时长
+/regions - 查看各地区的节点统计, 点击地区查看节点列表
+/top cpu|ram|disk|net|conn|load - 查看当前负载最高的节点
+/compare NODE_ID NODE_ID - 对比两个节点的硬件与实时状态
+/find os=debian arch=arm64 kernel~5.10 mem>2G - 按条件查找节点
+/history NODE_ID [6h|3d] [cpu|ram|load|net|conn|ping] - 查看主控记录的历史负载与延迟
+/latency [NODE_ID] - 查看节点各延迟目标的延迟与丢包, 不指定节点时列出延迟最高的目标
+/latency_alert [300ms] [10%|on|off] - 设置延迟与丢包告警
+/export csv|json|xlsx - 导出节点信息与实时状态
 
 /generate_notification_token - 生成通知令牌
-",
-            )
-            .reply_parameters(ReplyParameters::new(msg.id))
-            .disable_link_preview(true)
-            .await?;
-            Ok(())
-        }
-        Command::Connect { http_url } => {
-            let db_pool = DB_POOL
-                .get()
-                .unwrap_or_else(|| panic!("数据库连接池未初始化"));
-
-            let telegram_id = if let Some(user) = msg.clone().from {
-                user.id.0 as i64
-            } else {
-                return Ok(());
-            };
-
-            let url = match Url::parse(&http_url) {
-                Ok(url) => url,
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("无效的 URL: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-            };
 
-            let host = match url.host_str() {
-                None => {
-                    bot.send_message(msg.chat.id, "无效的 URL")
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-                Some(host) => host,
-            };
+/mute - 查看静音与维护窗口
+/mute NODE_ID|all 2h - 静音节点通知一段时间
+/mute NODE_ID|all until 03:00 - 静音节点通知至指定时间
+/unmute [NODE_ID|all] - 取消静音
+/maintenance add NODE_ID|all 02:00-04:00 [daily|weekdays|mon,wed] - 添加周期性维护窗口
+/maintenance del ID - 删除维护窗口
+
+/digest - 查看本聊天的定时报告
+/digest daily 09:00 - 每天定时发送报告
+/digest weekly mon 09:00 [csv|json|xlsx] - 每周定时发送报告, 可附带节点导出文件
+/digest off - 关闭定时报告
+
+/quota - 查看流量配额设置
+/quota NODE_ID 1T [重置日] [up|down|sum|max] - 设置每月流量配额
+/quota NODE_ID off - 删除流量配额
+/traffic - 查看本周期流量用量与预测
+
+/expiring - 查看 30 天内到期的节点
+/expiry_remind [30,7,1|on|off] - 设置续费提醒
+
+/cost - 查看按分组、地区、标签统计的费用
+/billing NODE_ID month|quarter|half|year|N [货币] - 设置节点计费周期与货币
+/rate USD 7.2 - 设置汇率
+/rate base CNY - 设置基准货币
+
+/template - 查看状态卡片模板的用法
+/template status|overview 模板 - 自定义节点卡片或总览中的字段
+/template status|overview reset - 恢复内置模板
+
+/units - 查看单位设置
+/units iec|si bit|byte 0-4 - 设置容量进制、速率单位与小数位数
+
+/export_settings - 导出本账号的连接与设置 (仅私聊\)
+/import_settings - 回复设置文件或随文件发送以导入 (仅私聊\)
+/backup - 备份 Bot 数据库 (仅 Bot 管理员\)
+
+/permission - 查看本群的命令权限设置
+/permission COMMAND admin|everyone|default - 设置命令权限 (仅群组管理员)
+";
+
+fn start_message() -> String {
+    let mut message = Markup::default();
+    message
+        .text("欢迎使用 Komari Unofficial Telegram Bot")
+        .line()
+        .line()
+        .text("输入 /help 查看使用方法")
+        .line()
+        .line()
+        .text("本 Bot 开源于 ")
+        .link(
+            "Github",
+            "https://github.com/GenshinMinecraft/komari-tg-bot",
+        )
+        .text(", 使用强力的 ")
+        .link("Rust", "https://www.rust-lang.org/")
+        .text(" 驱动, 爱来自 ")
+        .link("Komari", "https://github.com/komari-monitor/komari");
+    message.build()
+}
 
-            let port = match url.port() {
-                None => String::new(),
-                Some(port) => format!(":{port}"),
-            };
+async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
+    let Some(telegram_id) = msg
+        .from
+        .as_ref()
+        .filter(|user| !user.is_channel())
+        .and_then(|user| i64::try_from(user.id.0).ok())
+    else {
+        return Ok(());
+    };
 
-            let http_url = format!("{}://{}{}", url.scheme(), host, port);
+    match check_permission(&bot, &msg, cmd.name()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Reply::Text(String::from("此命令仅限群组管理员使用"))
+                .send(&bot, &msg)
+                .await;
+        }
+        Err(e) => {
+            return Reply::Text(format!("无法校验权限: {e}"))
+                .send(&bot, &msg)
+                .await;
+        }
+    }
 
-            let ws_scheme = match url.scheme() {
-                "http" => "ws",
-                "https" => "wss",
-                _ => {
-                    bot.send_message(msg.chat.id, "无效的 URL")
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-            };
+    let reply = match cmd {
+        Command::Connect { http_url } => return Box::pin(connect(&bot, &msg, &http_url)).await,
+        cmd => Box::pin(dispatch(&bot, &msg, telegram_id, cmd)).await,
+    };
 
-            let ws_url = format!("{ws_scheme}://{host}{port}");
+    reply.send(&bot, &msg).await
+}
 
-            match insert_monitor(
-                db_pool,
-                Monitor {
-                    telegram_id: telegram_id as u64,
-                    monitor_http_url: http_url,
-                    monitor_ws_url: ws_url,
+/// 交给各模块处理命令, 返回要发送的回复
+async fn dispatch(bot: &Bot, msg: &Message, telegram_id: i64, cmd: Command) -> Reply {
+    match cmd {
+        Command::Start => Reply::Formatted(start_message(), None),
+        Command::Help => Reply::Text(String::from(HELP)),
+        Command::Connect { .. } => unreachable!("由 answer 处理"),
+        Command::Disconnect => Reply::text(disconnect(msg).await, "取消连接到 Komari 失败"),
+        Command::Update => Reply::formatted(first_init_read(msg.clone()).await, "更新站点信息失败"),
+        Command::GetNodeId { filter } => get_node_id::command(telegram_id, &filter).await,
+        Command::TotalStatus { filter } => total_status::command(telegram_id, &filter).await,
+        Command::Status { node_id, group } => status::command(telegram_id, node_id, group).await,
+        Command::Groups => Reply::formatted(parse_ws_groups(telegram_id).await, "无法获取分组信息"),
+        Command::Offline => {
+            Reply::formatted(parse_ws_offline(telegram_id).await, "无法获取离线节点")
+        }
+        Command::Regions => {
+            Reply::keyboard(parse_ws_regions(telegram_id).await, "无法获取地区信息")
+        }
+        Command::Top { metric } => top::command(telegram_id, metric.as_deref()).await,
+        Command::Compare { left, right } => compare::command(telegram_id, left, right).await,
+        Command::Find { args } => find::command(telegram_id, &args).await,
+        Command::History { args } => Reply::formatted(
+            history::history(telegram_id, &args).await,
+            "无法获取历史记录",
+        ),
+        Command::Latency { node } => Reply::formatted(
+            latency::latency(telegram_id, node.as_deref()).await,
+            "无法获取延迟",
+        ),
+        Command::LatencyAlert { args } => Reply::text(
+            latency::latency_alert(telegram_id, &args).await,
+            "无法设置延迟告警",
+        ),
+        Command::Export { format } => export::command(telegram_id, format.as_deref()).await,
+        Command::Backup => backup::backup_command(msg, telegram_id).await,
+        Command::ExportSettings => backup::export_settings_command(msg, telegram_id).await,
+        Command::ImportSettings => backup::import_settings_command(bot, msg, telegram_id).await,
+        Command::GenerateNotificationToken => Reply::formatted(
+            generate_notification_token(msg.clone()).await,
+            "无法生成通知令牌",
+        ),
+        Command::Permission { command, level } => {
+            permission::command(msg, command, level, Command::NAMES).await
+        }
+        Command::Mute { args } => Reply::text(mute::mute(telegram_id, &args).await, "无法设置静音"),
+        Command::Unmute { target } => Reply::text(
+            mute::unmute(telegram_id, target.as_ref()).await,
+            "无法取消静音",
+        ),
+        Command::Maintenance { args } => Reply::text(
+            mute::maintenance(telegram_id, &args).await,
+            "无法设置维护窗口",
+        ),
+        Command::Digest { args } => Reply::text(
+            digest::digest(telegram_id, msg.chat.id.0, &args).await,
+            "无法设置定时报告",
+        ),
+        Command::Quota { args } => {
+            Reply::text(quota::quota(telegram_id, &args).await, "无法设置流量配额")
+        }
+        Command::Traffic => Reply::formatted(quota::traffic(telegram_id).await, "无法获取流量用量"),
+        Command::Expiring => {
+            Reply::formatted(expiry::expiring(telegram_id).await, "无法获取到期信息")
+        }
+        Command::ExpiryRemind { args } => Reply::text(
+            expiry::expiry_remind(telegram_id, &args).await,
+            "无法设置续费提醒",
+        ),
+        Command::Cost => Reply::formatted(cost::cost(telegram_id).await, "无法统计费用"),
+        Command::Billing { args } => {
+            Reply::text(cost::billing(telegram_id, &args).await, "无法设置计费信息")
+        }
+        Command::Rate { args } => Reply::text(cost::rate(telegram_id, &args).await, "无法设置汇率"),
+        Command::Template { kind, body } => Reply::text(
+            template::template(telegram_id, kind.as_deref(), body.as_deref()).await,
+            "无法设置模板",
+        ),
+        Command::Units { args } => {
+            Reply::text(units::units(telegram_id, &args).await, "无法设置单位")
+        }
+    }
+}
 
-                    total_server_count: Default::default(),
-                    site_name: Default::default(),
-                    site_description: Default::default(),
-                    komari_version: Default::default(),
-                    notification_token: None,
-                },
-            )
-            .await
-            {
-                Ok(()) => {
-                    bot.send_message(msg.chat.id, "已保存监控信息")
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-
-                    match first_init_read(msg.clone()).await {
-                        Ok(message) => {
-                            bot.send_message(msg.chat.id, message)
-                                .parse_mode(ParseMode::MarkdownV2)
-                                .reply_parameters(ReplyParameters::new(msg.id))
-                                .await?;
-                        }
-                        Err(e) => {
-                            bot.send_message(
-                                msg.chat.id,
-                                format!("获取站点信息失败，已自动删除用户信息: {e}"),
-                            )
-                            .reply_parameters(ReplyParameters::new(msg.id))
-                            .await?;
-
-                            match delete_monitor(db_pool, msg.clone()).await {
-                                Ok(()) => return Ok(()),
-                                Err(e) => {
-                                    bot.send_message(
-                                        msg.chat.id,
-                                        format!("取消连接到 Komari 失败: {e}"),
-                                    )
-                                    .reply_parameters(ReplyParameters::new(msg.id))
-                                    .await?;
-                                }
-                            }
-                        }
-                    }
+/// 保存连接后读取站点信息, 读取失败时删除刚保存的连接
+async fn connect(bot: &Bot, msg: &Message, http_url: &str) -> ResponseResult<()> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let monitor = match monitor_urls(http_url) {
+        Ok((monitor_http_url, monitor_ws_url)) => Monitor {
+            telegram_id: msg.from.as_ref().map_or(0, |user| user.id.0),
+            monitor_http_url,
+            monitor_ws_url,
+            total_server_count: Default::default(),
+            site_name: String::default(),
+            site_description: String::default(),
+            komari_version: String::default(),
+            notification_token: None,
+        },
+        Err(e) => return Reply::Text(e).send(bot, msg).await,
+    };
 
-                    Ok(())
-                }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("保存监控信息失败: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    Ok(())
-                }
-            }
-        }
-        Command::Disconnect => {
-            let db_pool = DB_POOL
-                .get()
-                .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    if let Err(e) = insert_monitor(db_pool, monitor).await {
+        return Reply::Text(format!("保存监控信息失败: {e}"))
+            .send(bot, msg)
+            .await;
+    }
+    Reply::Text(String::from("已保存监控信息"))
+        .send(bot, msg)
+        .await?;
+
+    match Box::pin(first_init_read(msg.clone())).await {
+        Ok(message) => Reply::Formatted(message, None).send(bot, msg).await,
+        Err(e) => {
+            Reply::Text(format!("获取站点信息失败，已自动删除用户信息: {e}"))
+                .send(bot, msg)
+                .await?;
 
             match delete_monitor(db_pool, msg.clone()).await {
-                Ok(()) => {
-                    bot.send_message(msg.chat.id, "已取消连接到 Komari")
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    Ok(())
-                }
+                Ok(()) => Ok(()),
                 Err(e) => {
-                    bot.send_message(msg.chat.id, format!("取消连接到 Komari 失败: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    Ok(())
+                    Reply::Text(format!("取消连接到 Komari 失败: {e}"))
+                        .send(bot, msg)
+                        .await
                 }
             }
         }
-        Command::Update => {
-            match first_init_read(msg.clone()).await {
-                Ok(message) => {
-                    bot.send_message(msg.chat.id, message)
-                        .parse_mode(ParseMode::MarkdownV2)
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("更新站点信息失败: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                }
-            }
+    }
+}
 
-            Ok(())
-        }
-        Command::GetNodeId => match ws_get_node_id(msg.clone()).await {
-            Ok(message) => {
-                bot.send_message(msg.chat.id, message)
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .reply_parameters(ReplyParameters::new(msg.id))
-                    .await?;
-                Ok(())
-            }
-            Err(e) => {
-                bot.send_message(msg.chat.id, format!("无法获取节点ID: {e}"))
-                    .reply_parameters(ReplyParameters::new(msg.id))
-                    .await?;
-                Ok(())
-            }
-        },
-        Command::TotalStatus => {
-            let telegram_id = if let Some(user) = msg.from {
-                user.id.0 as i64
-            } else {
-                return Ok(());
-            };
+/// 由 HTTP URL 得到保存的 (HTTP URL, Websocket URL), 去除路径部分
+fn monitor_urls(http_url: &str) -> Result<(String, String), ErrorString> {
+    let url = Url::parse(http_url).map_err(|e| format!("无效的 URL: {e}"))?;
+    let host = url.host_str().ok_or("无效的 URL")?;
 
-            let message_str = match parse_ws_total_status(telegram_id).await {
-                Ok(message_str) => message_str,
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("无法解析 Komari Websocket 数据: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-            };
+    let port = match url.port() {
+        None => String::new(),
+        Some(port) => format!(":{port}"),
+    };
 
-            bot.send_message(msg.chat.id, message_str)
-                .parse_mode(ParseMode::MarkdownV2)
-                .reply_parameters(ReplyParameters::new(msg.id))
-                .disable_link_preview(true)
-                .await?;
+    let ws_scheme = match url.scheme() {
+        "http" => "ws",
+        "https" => "wss",
+        _ => return Err(String::from("无效的 URL")),
+    };
 
-            Ok(())
-        }
-        Command::Status { node_id } => {
-            let telegram_id = if let Some(user) = msg.from {
-                user.id.0 as i64
-            } else {
-                return Ok(());
-            };
+    Ok((
+        format!("{}://{host}{port}", url.scheme()),
+        format!("{ws_scheme}://{host}{port}"),
+    ))
+}
 
-            let msg_str = match parse_ws_single_server_by_index(telegram_id, node_id).await {
-                Ok(msg) => msg,
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("无法解析 Komari 数据: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-            };
+async fn disconnect(msg: &Message) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
 
-            let keyboard = match make_keyboard_for_single(node_id, telegram_id).await {
-                Ok(key) => key,
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("无法生成键盘: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-            };
-
-            bot.send_message(msg.chat.id, msg_str)
-                .parse_mode(ParseMode::MarkdownV2)
-                .reply_parameters(ReplyParameters::new(msg.id))
-                .reply_markup(keyboard)
-                .disable_link_preview(true)
-                .await?;
+    delete_monitor(db_pool, msg.clone()).await?;
+    Ok(String::from("已取消连接到 Komari"))
+}
 
-            Ok(())
-        }
-        Command::GenerateNotificationToken => {
-            if !msg.chat.is_private() {
-                bot.send_message(msg.chat.id, "此命令只能用于私聊")
-                    .reply_parameters(ReplyParameters::new(msg.id))
-                    .await?;
-                return Ok(());
+/// 原地编辑按钮所在的消息
+async fn edit(
+    bot: &Bot,
+    q: &CallbackQuery,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(message) = q.regular_message() {
+        let request = bot.edit_text(message, text);
+        match keyboard {
+            Some(keyboard) => {
+                request
+                    .reply_markup(keyboard)
+                    .parse_mode(parse_mode())
+                    .disable_link_preview(true)
+                    .await?
             }
-
-            match generate_notification_token(msg.clone()).await {
-                Ok(message) => {
-                    bot.send_message(msg.chat.id, message)
-                        .parse_mode(ParseMode::MarkdownV2)
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("无法生成通知令牌: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                }
+            None => request.await?,
+        };
+    } else if let Some(id) = &q.inline_message_id {
+        let request = bot.edit_message_text_inline(id, text);
+        match keyboard {
+            Some(keyboard) => {
+                request
+                    .reply_markup(keyboard)
+                    .parse_mode(parse_mode())
+                    .await?
             }
-
-            Ok(())
-        }
+            None => request.await?,
+        };
     }
+
+    Ok(())
 }
 
-async fn callback_handler(bot: Bot, q: CallbackQuery) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if let Some(ref node_id) = q.data {
-        bot.answer_callback_query(q.id.clone()).await?;
+/// 按钮对应的列表或节点卡片
+async fn callback_view(
+    data: CallbackData,
+) -> Result<Result<(String, InlineKeyboardMarkup), ErrorString>, ErrorString> {
+    let telegram_id = data.instance;
+    let arg = data.arg.as_deref().unwrap_or_default();
+
+    // 节点列表、地区列表、地区下钻与查找结果翻页
+    let list = match data.action {
+        CallbackAction::Nodes => match NodeFilter::from_text(arg) {
+            Ok(filter) => ws_get_node_id(telegram_id, &filter, data.page).await,
+            Err(e) => Err(e),
+        },
+        CallbackAction::Find => match NodeQuery::from_text(arg) {
+            Ok(query) => parse_ws_find(telegram_id, &query, data.page).await,
+            Err(e) => Err(e),
+        },
+        CallbackAction::Regions => parse_ws_regions(telegram_id).await,
+        CallbackAction::Region => {
+            let region = data.arg.as_deref().ok_or("Invalid callback data")?;
+            parse_ws_region_nodes(telegram_id, region).await
+        }
+        CallbackAction::Node => {
+            let node_id = data.node.ok_or("Invalid callback data")?;
+            // 节点卡片的页码即标签页, 切换标签时原地编辑
+            let tab = NodeTab::from_page(data.page);
+
+            return Ok(
+                match parse_ws_single_server_by_index(telegram_id, node_id, tab).await {
+                    Ok(card) => Ok((
+                        card,
+                        make_keyboard_for_single(node_id, telegram_id, data.arg.as_deref(), tab)
+                            .await?,
+                    )),
+                    Err(e) => Err(format!("无法解析 Komari 数据: {e}")),
+                },
+            );
+        }
+    };
 
-        let (callback_tg_id, node_id) = {
-            let split: Vec<String> = node_id
-                .split('-')
-                .map(std::string::ToString::to_string)
-                .collect();
-            (
-                split
-                    .first()
-                    .ok_or("Invalid callback data".to_string())?
-                    .clone(),
-                split
-                    .get(1)
-                    .ok_or("Invalid callback data".to_string())?
-                    .clone(),
-            )
-        };
+    Ok(list.map_err(|e| format!("无法获取列表: {e}")))
+}
 
-        let telegram_id = callback_tg_id
-            .parse::<i64>()
-            .map_err(|_| "Invalid callback data".to_string())?;
-        let node_id = node_id
-            .parse::<i32>()
-            .map_err(|_| "Invalid callback data".to_string())?;
+async fn callback_handler(bot: Bot, q: CallbackQuery) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(data) = q.data.as_deref() else {
+        return Ok(());
+    };
 
-        if telegram_id != q.from.id.0 as i64 {
+    let data = match CallbackData::decode(data) {
+        Ok(data) => data,
+        Err(e) => {
+            bot.answer_callback_query(q.id.clone()).text(e).await?;
             return Ok(());
         }
+    };
 
-        let msg_str = match parse_ws_single_server_by_index(telegram_id, node_id).await {
-            Ok(msg) => msg,
-            Err(e) => {
-                if let Some(message) = q.regular_message() {
-                    bot.edit_text(message, format!("无法解析 Komari 数据: {e}"))
-                        .await?;
-                } else if let Some(id) = q.inline_message_id {
-                    bot.edit_message_text_inline(id, format!("无法解析 Komari 数据: {e}"))
-                        .await?;
-                }
-
-                return Ok(());
-            }
-        };
+    bot.answer_callback_query(q.id.clone()).await?;
 
-        if let Some(message) = q.regular_message() {
-            bot.edit_text(message, msg_str)
-                .reply_markup(make_keyboard_for_single(node_id, q.from.id.0 as i64).await?)
-                .parse_mode(ParseMode::MarkdownV2)
-                .disable_link_preview(true)
-                .await?;
-        } else if let Some(id) = q.inline_message_id {
-            bot.edit_message_text_inline(id, msg_str)
-                .reply_markup(make_keyboard_for_single(node_id, q.from.id.0 as i64).await?)
-                .parse_mode(ParseMode::MarkdownV2)
-                .await?;
-        }
+    if i64::try_from(q.from.id.0).ok() != Some(data.instance) {
+        return Ok(());
     }
 
-    Ok(())
+    match Box::pin(callback_view(data)).await? {
+        Ok((text, keyboard)) => edit(&bot, &q, text, Some(keyboard)).await,
+        Err(e) => edit(&bot, &q, e, None).await,
+    }
 }
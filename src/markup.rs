@@ -0,0 +1,290 @@
+use std::env;
+use teloxide::types::ParseMode;
+
+/// `MarkdownV2` 中需要转义的字符
+const MARKDOWN_SPECIAL: &str = "_*[]()~`>#+-=|{}.!\\";
+
+/// 长列表最多列出的条目数, 避免大量节点时超出 Telegram 4096 字符的消息长度限制
+pub const LIST_LIMIT: usize = 20;
+
+/// 按解析模式拼接消息, 文字与插入的值在写入时分别转义
+///
+/// 格式本身 (等宽、代码块) 由方法决定, 值中的任何字符都不会被当作格式
+#[derive(Debug, Clone)]
+pub struct Markup {
+    mode: ParseMode,
+    text: String,
+}
+
+impl Markup {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            text: String::new(),
+        }
+    }
+
+    /// 普通文字
+    pub fn text(&mut self, text: impl AsRef<str>) -> &mut Self {
+        self.text.push_str(&escape(text.as_ref(), self.mode));
+        self
+    }
+
+    /// 等宽文字, 用于数值与名称
+    pub fn code(&mut self, text: impl AsRef<str>) -> &mut Self {
+        self.text.push_str(&code(text.as_ref(), self.mode));
+        self
+    }
+
+    /// 链接, 地址中的 `)` 与 `\` 在 `MarkdownV2` 中需要转义
+    pub fn link(&mut self, text: impl AsRef<str>, url: &str) -> &mut Self {
+        let link = match self.mode {
+            ParseMode::Html => format!(
+                "<a href=\"{}\">{}</a>",
+                escape(url, self.mode).replace('"', "&quot;"),
+                escape(text.as_ref(), self.mode)
+            ),
+            _ => format!(
+                "[{}]({})",
+                escape(text.as_ref(), self.mode),
+                url.replace('\\', r"\\").replace(')', r"\)")
+            ),
+        };
+        self.text.push_str(&link);
+        self
+    }
+
+    /// 代码块, 用于等宽表格
+    pub fn pre(&mut self, text: impl AsRef<str>) -> &mut Self {
+        let (open, text, close) = match self.mode {
+            ParseMode::Html => ("<pre>", escape(text.as_ref(), self.mode), "</pre>"),
+            _ => ("```\n", escape_code(text.as_ref()), "```"),
+        };
+        self.text.push_str(open);
+        self.text.push_str(&text);
+        self.text.push_str(close);
+        self
+    }
+
+    /// `标签: 值` 一行, 值以等宽显示
+    pub fn field(&mut self, label: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        self.text(format!("{}: ", label.as_ref()))
+            .code(value)
+            .line()
+    }
+
+    /// 列表超出 [`LIST_LIMIT`] 时注明未列出的数量
+    pub fn remaining(&mut self, len: usize) -> &mut Self {
+        if len > LIST_LIMIT {
+            self.text(format!("另有 {} 个", len - LIST_LIMIT)).line();
+        }
+        self
+    }
+
+    pub fn line(&mut self) -> &mut Self {
+        self.text.push('\n');
+        self
+    }
+
+    /// 追加另一段以相同模式构建的内容
+    pub fn append(&mut self, other: &Markup) -> &mut Self {
+        self.text.push_str(&other.text);
+        self
+    }
+
+    pub fn build(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// 使用配置中 `parse_mode` 的消息
+impl Default for Markup {
+    fn default() -> Self {
+        Self::new(parse_mode())
+    }
+}
+
+/// 发送消息时使用的解析模式, 在 config.json 中以 `parse_mode` 设置为 `markdown` 或 `html`
+pub fn parse_mode() -> ParseMode {
+    match env::var("PARSE_MODE").as_deref() {
+        Ok("html") => ParseMode::Html,
+        _ => ParseMode::MarkdownV2,
+    }
+}
+
+pub fn escape(text: &str, mode: ParseMode) -> String {
+    if mode == ParseMode::Html {
+        return text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+    }
+
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if MARKDOWN_SPECIAL.contains(char) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+/// 等宽实体中只需转义反引号与反斜杠
+fn escape_code(text: &str) -> String {
+    text.replace('\\', r"\\").replace('`', r"\`")
+}
+
+pub fn code(text: &str, mode: ParseMode) -> String {
+    match mode {
+        ParseMode::Html => format!("<code>{}</code>", escape(text, mode)),
+        // 空的等宽实体会被 Telegram 拒绝
+        _ if text.is_empty() => String::from("` `"),
+        _ => format!("`{}`", escape_code(text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 节点名称与描述中常见的特殊字符
+    fn card(mode: ParseMode) -> String {
+        let mut message = Markup::new(mode);
+        message
+            .text("web*01 | a_b[c]")
+            .line()
+            .field("描述", "run `ls` <now> & exit")
+            .text("面板: ")
+            .link("Komari [dev]", "https://example.com/a_(b)?q=\"x\"&y=1")
+            .line()
+            .code("")
+            .line()
+            .pre("name  | ram\nweb*01| 1.5G\\s\n");
+        message.build()
+    }
+
+    #[test]
+    fn golden_markdown() {
+        assert_eq!(
+            card(ParseMode::MarkdownV2),
+            concat!(
+                "web\\*01 \\| a\\_b\\[c\\]\n",
+                "描述: `run \\`ls\\` <now> & exit`\n",
+                "面板: [Komari \\[dev\\]](https://example.com/a_(b\\)?q=\"x\"&y=1)\n",
+                "` `\n",
+                "```\nname  | ram\nweb*01| 1.5G\\\\s\n```",
+            )
+        );
+    }
+
+    #[test]
+    fn remaining_counts_entries_past_the_limit() {
+        let mut message = Markup::new(ParseMode::Html);
+        message.remaining(LIST_LIMIT);
+        assert_eq!(message.build(), "");
+
+        message.remaining(LIST_LIMIT + 25);
+        assert_eq!(message.build(), "另有 25 个\n");
+    }
+
+    #[test]
+    fn golden_html() {
+        assert_eq!(
+            card(ParseMode::Html),
+            concat!(
+                "web*01 | a_b[c]\n",
+                "描述: <code>run `ls` &lt;now&gt; &amp; exit</code>\n",
+                "面板: <a href=\"https://example.com/a_(b)?q=&quot;x&quot;&amp;y=1\">Komari [dev]</a>\n",
+                "<code></code>\n",
+                "<pre>name  | ram\nweb*01| 1.5G\\s\n</pre>",
+            )
+        );
+    }
+
+    /// 固定种子的 xorshift, 生成包含全部保留字符的随机文字
+    fn fuzz_inputs() -> impl Iterator<Item = String> {
+        const ALPHABET: &[char] = &[
+            '_', '*', '[', ']', '(', ')', '~', '`', '>', '#', '+', '-', '=', '|', '{', '}', '.',
+            '!', '\\', '<', '&', ';', '"', 'a', 'Z', '0', ' ', '\n', '节', '点', '🚀',
+        ];
+
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        (0..2000).map(move |_| {
+            let len = usize::try_from(next() % 40).unwrap();
+            (0..len)
+                .map(|_| ALPHABET[usize::try_from(next()).unwrap() % ALPHABET.len()])
+                .collect()
+        })
+    }
+
+    /// 每个保留字符前都有转义符, 转义符只用于保留字符, 去掉转义后与原文相同
+    fn unescape(escaped: &str, reserved: &str) -> Option<String> {
+        let mut text = String::new();
+        let mut chars = escaped.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => {
+                    let next = chars.next().filter(|next| reserved.contains(*next))?;
+                    text.push(next);
+                }
+                char if reserved.contains(char) => return None,
+                char => text.push(char),
+            }
+        }
+        Some(text)
+    }
+
+    #[test]
+    fn fuzz_markdown_escape() {
+        for input in fuzz_inputs() {
+            let escaped = escape(&input, ParseMode::MarkdownV2);
+            assert_eq!(
+                unescape(&escaped, MARKDOWN_SPECIAL).as_ref(),
+                Some(&input),
+                "{escaped}"
+            );
+        }
+    }
+
+    #[test]
+    fn fuzz_code_escape() {
+        for input in fuzz_inputs() {
+            let escaped = escape_code(&input);
+            assert_eq!(
+                unescape(&escaped, "`\\").as_ref(),
+                Some(&input),
+                "{escaped}"
+            );
+        }
+    }
+
+    #[test]
+    fn fuzz_html_escape() {
+        for input in fuzz_inputs() {
+            let escaped = escape(&input, ParseMode::Html);
+            assert!(!escaped.contains(['<', '>']), "{escaped}");
+            assert!(
+                escaped.match_indices('&').all(|(i, _)| {
+                    ["&amp;", "&lt;", "&gt;"]
+                        .iter()
+                        .any(|entity| escaped[i..].starts_with(entity))
+                }),
+                "{escaped}"
+            );
+
+            let unescaped = escaped
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&");
+            assert_eq!(unescaped, input);
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::ApiNodes;
+use crate::connection::api_public::ApiPublic;
+use crate::connection::api_records::{ApiPingRecords, ApiRecords};
+use crate::connection::api_version::ApiVersion;
+use crate::connection::client::{KomariApi, KomariClient};
+use crate::connection::filter::NodeFilter;
+use crate::connection::first_init_read;
+use crate::connection::ws_get::ApiWs;
+use crate::connection::ws_get::status::{NodeTab, parse_ws_single_server_by_index, single_server};
+use crate::connection::ws_get::total_status::{parse_ws_total_status, total_status};
+use crate::db::{DB_POOL, Monitor, connect_db, create_table, insert_monitor};
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use futures::future::join_all;
+use futures::{SinkExt, StreamExt};
+use log::error;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use teloxide::types::Message;
+use tokio::net::TcpListener;
+use tokio::sync::OnceCell;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::{Message as WsMessage, Utf8Bytes};
+
+const PUBLIC: &str = include_str!("../fixtures/komari/public.json");
+const NODES: &str = include_str!("../fixtures/komari/nodes.json");
+const NODES_DRIFT: &str = include_str!("../fixtures/komari/nodes_drift.json");
+const VERSION: &str = include_str!("../fixtures/komari/version.json");
+const CLIENTS: &str = include_str!("../fixtures/komari/clients.json");
+const CLIENTS_DRIFT: &str = include_str!("../fixtures/komari/clients_drift.json");
+
+/// 测试中请求主控的超时时间 (秒)
+const REQUEST_TIMEOUT_SECS: &str = "1";
+
+/// 超过测试中的请求超时时间
+const SLOW_DELAY: Duration = Duration::from_secs(2);
+
+/// 模拟主控的行为
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    /// 正常返回固定数据
+    Normal,
+    /// 开启私有模式, 所有接口返回 401
+    Private,
+    /// 新版主控: 节点带有未知字段, Websocket 数据缺少字段
+    SchemaDrift,
+    /// 每次响应前等待 `SLOW_DELAY`
+    Slow,
+}
+
+impl Scenario {
+    async fn respond(self, body: &'static str) -> impl IntoResponse {
+        if self == Self::Slow {
+            tokio::time::sleep(SLOW_DELAY).await;
+        }
+
+        if self == Self::Private {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::CONTENT_TYPE, "application/json")],
+                r#"{"status":"error","message":"Unauthorized."}"#,
+            );
+        }
+
+        (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/json")],
+            body,
+        )
+    }
+}
+
+/// 在本机随机端口上运行的模拟主控
+pub struct MockKomari {
+    pub http_url: String,
+    pub ws_url: String,
+    /// 收到的 `/api/nodes` 请求数
+    pub nodes_requests: Arc<AtomicUsize>,
+}
+
+/// 启动 HTTP 与 Websocket 服务, 随测试的运行时退出
+pub async fn start(scenario: Scenario) -> Result<MockKomari, ErrorString> {
+    let http_listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("无法监听端口: {e}"))?;
+    let ws_listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("无法监听端口: {e}"))?;
+
+    let http_addr = http_listener
+        .local_addr()
+        .map_err(|e| format!("无法获取监听地址: {e}"))?;
+    let ws_addr = ws_listener
+        .local_addr()
+        .map_err(|e| format!("无法获取监听地址: {e}"))?;
+
+    let nodes_requests = Arc::new(AtomicUsize::new(0));
+    let counter = nodes_requests.clone();
+
+    let app = Router::new()
+        .route(
+            "/api/public",
+            get(|State(scenario): State<Scenario>| scenario.respond(PUBLIC)),
+        )
+        .route(
+            "/api/nodes",
+            get(|State(scenario): State<Scenario>| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body = if scenario == Scenario::SchemaDrift {
+                    NODES_DRIFT
+                } else {
+                    NODES
+                };
+                scenario.respond(body).await
+            }),
+        )
+        .route(
+            "/api/version",
+            get(|State(scenario): State<Scenario>| scenario.respond(VERSION)),
+        )
+        .with_state(scenario);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(http_listener, app).await {
+            error!("模拟主控: HTTP 服务异常退出: {e}");
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = ws_listener.accept().await {
+            tokio::spawn(serve_ws(stream, scenario));
+        }
+    });
+
+    Ok(MockKomari {
+        http_url: format!("http://{http_addr}"),
+        ws_url: format!("ws://{ws_addr}"),
+        nodes_requests,
+    })
+}
+
+/// `/api/clients`: 收到 `get` 后返回一帧节点数据
+// 握手回调的签名由 tungstenite 决定
+#[allow(clippy::result_large_err)]
+async fn serve_ws(stream: tokio::net::TcpStream, scenario: Scenario) {
+    let callback = move |_: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if scenario == Scenario::Private {
+            let mut rejection = ErrorResponse::new(Some(String::from("Unauthorized.")));
+            *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+            return Err(rejection);
+        }
+        Ok(response)
+    };
+
+    let Ok(mut ws) = tokio_tungstenite::accept_hdr_async(stream, callback).await else {
+        return;
+    };
+
+    while let Some(Ok(message)) = ws.next().await {
+        if message.to_text().ok() != Some("get") {
+            continue;
+        }
+
+        if scenario == Scenario::Slow {
+            tokio::time::sleep(SLOW_DELAY).await;
+        }
+
+        let body = if scenario == Scenario::SchemaDrift {
+            CLIENTS_DRIFT
+        } else {
+            CLIENTS
+        };
+        if ws
+            .send(WsMessage::Text(Utf8Bytes::from_static(body)))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// 直接返回固定数据的 [`KomariApi`] 实现, 不经过网络
+pub struct FakeKomari {
+    monitor: Monitor,
+}
+
+impl FakeKomari {
+    pub fn new(telegram_id: i64) -> Self {
+        Self {
+            monitor: Monitor {
+                telegram_id: telegram_id.unsigned_abs(),
+                monitor_http_url: String::new(),
+                monitor_ws_url: String::new(),
+                total_server_count: 2,
+                site_name: String::from("Mock Komari"),
+                site_description: String::new(),
+                komari_version: String::new(),
+                notification_token: None,
+            },
+        }
+    }
+}
+
+fn parse_fixture<T: DeserializeOwned>(text: &str) -> Result<T, ErrorString> {
+    serde_json::from_str(text).map_err(|e| format!("JSON 解析错误: {e}"))
+}
+
+impl KomariApi for FakeKomari {
+    fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    async fn public(&self) -> Result<ApiPublic, ErrorString> {
+        parse_fixture(PUBLIC)
+    }
+
+    async fn nodes(&self) -> Result<ApiNodes, ErrorString> {
+        parse_fixture(NODES)
+    }
+
+    async fn version(&self) -> Result<ApiVersion, ErrorString> {
+        parse_fixture(VERSION)
+    }
+
+    async fn snapshot(&self) -> Result<ApiWs, ErrorString> {
+        parse_fixture(CLIENTS)
+    }
+
+    async fn records(&self, _uuid: &str, _hours: u32) -> Result<ApiRecords, ErrorString> {
+        parse_fixture(r#"{"status":"success","data":{"records":[]}}"#)
+    }
+
+    async fn ping_records(&self, _uuid: &str, _hours: u32) -> Result<ApiPingRecords, ErrorString> {
+        parse_fixture(r#"{"status":"success","data":{"records":[],"tasks":[]}}"#)
+    }
+}
+
+/// 所有测试共用的临时数据库与请求超时时间, 只初始化一次
+static SETUP: OnceCell<()> = OnceCell::const_new();
+
+async fn setup() {
+    SETUP
+        .get_or_init(|| async {
+            // SAFETY: 在任何请求之前设置, 测试中没有其他代码修改环境变量
+            unsafe {
+                std::env::set_var("REQUEST_TIMEOUT_SECS", REQUEST_TIMEOUT_SECS);
+            }
+
+            let db_file =
+                std::env::temp_dir().join(format!("komari-tgbot-test-{}.db", uuid::Uuid::new_v4()));
+            let db_url = format!("{}?mode=rwc", db_file.display());
+            let pool = connect_db(&db_url).await.unwrap();
+            create_table(pool).await.unwrap();
+        })
+        .await;
+}
+
+/// 启动该场景的模拟主控, 并保存为该用户的连接
+async fn connect(scenario: Scenario, telegram_id: i64) -> MockKomari {
+    setup().await;
+    let db_pool = DB_POOL.get().unwrap();
+    let mock = start(scenario).await.unwrap();
+
+    insert_monitor(
+        db_pool,
+        Monitor {
+            telegram_id: telegram_id.unsigned_abs(),
+            monitor_http_url: mock.http_url.clone(),
+            monitor_ws_url: mock.ws_url.clone(),
+            total_server_count: 0,
+            site_name: String::from("Mock Komari"),
+            site_description: String::new(),
+            komari_version: String::new(),
+            notification_token: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    mock
+}
+
+/// 以该用户身份发出的私聊 `/update`
+fn private_message(telegram_id: i64) -> Message {
+    serde_json::from_value(serde_json::json!({
+        "message_id": 1,
+        "date": 0,
+        "chat": { "id": telegram_id, "type": "private", "first_name": "test" },
+        "from": { "id": telegram_id, "is_bot": false, "first_name": "test" },
+        "text": "/update",
+    }))
+    .unwrap()
+}
+
+/// `/update`、`/total_status` 与 `/status 1` 的结果
+async fn run_commands(telegram_id: i64) -> [Result<String, ErrorString>; 3] {
+    [
+        first_init_read(private_message(telegram_id)).await,
+        parse_ws_total_status(telegram_id, &NodeFilter::default()).await,
+        parse_ws_single_server_by_index(telegram_id, 1, NodeTab::Overview).await,
+    ]
+}
+
+fn assert_contains(result: &Result<String, ErrorString>, parts: &[&str]) {
+    let text = result.as_ref().unwrap();
+    for part in parts {
+        assert!(text.contains(part), "缺少 {part:?}: {text}");
+    }
+}
+
+#[tokio::test]
+async fn normal_panel() {
+    let telegram_id = 1;
+    let mock = connect(Scenario::Normal, telegram_id).await;
+
+    let [update, total, single] = run_commands(telegram_id).await;
+    assert_contains(
+        &update,
+        &["Mock Komari", "节点数量: `2`", "CPU 核心总数: `6`"],
+    );
+    assert_contains(&total, &["Mock Komari"]);
+    assert_contains(&single, &[r"HK\-Web\_01"]);
+
+    // 节点信息在缓存有效期内只请求一次, 清除缓存后并发的请求共用一次请求
+    let cached = mock.nodes_requests.load(Ordering::SeqCst);
+    assert_eq!(cached, 1);
+
+    let client = KomariClient::for_user(telegram_id).await.unwrap();
+    client.invalidate();
+    let results = join_all((0..5).map(|_| client.nodes())).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(mock.nodes_requests.load(Ordering::SeqCst) - cached, 1);
+}
+
+#[tokio::test]
+async fn private_panel() {
+    let telegram_id = 2;
+    connect(Scenario::Private, telegram_id).await;
+
+    let [update, total, single] = run_commands(telegram_id).await;
+    assert_eq!(update, Err(String::from("主控开启了私有模式")));
+    // 总览先检查 Websocket, 单节点先检查节点列表
+    assert_eq!(
+        total,
+        Err(String::from(
+            "无法连接到 Komari Websocket 服务器: HTTP error: 401 Unauthorized"
+        ))
+    );
+    assert_eq!(single, Err(String::from("主控开启了私有模式")));
+}
+
+#[tokio::test]
+async fn schema_drift() {
+    let telegram_id = 3;
+    connect(Scenario::SchemaDrift, telegram_id).await;
+
+    // 节点列表的未知字段被忽略, Websocket 数据缺少的字段无法解析
+    let drift = Err(String::from(
+        "无法将 Websocket 响应内容转化为 JSON: missing field `network` at line 40 column 7",
+    ));
+    let [update, total, single] = run_commands(telegram_id).await;
+    assert_contains(&update, &["Mock Komari", "节点数量: `2`"]);
+    assert_eq!(total, drift);
+    assert_eq!(single, drift);
+}
+
+#[tokio::test]
+async fn slow_panel_times_out() {
+    let telegram_id = 4;
+    connect(Scenario::Slow, telegram_id).await;
+
+    let [update, total, single] = run_commands(telegram_id).await;
+    assert_eq!(update, Err(String::from("请求主控超时")));
+    assert_eq!(total, Err(String::from("请求主控超时")));
+    assert_eq!(single, Err(String::from("请求主控超时")));
+}
+
+/// 以内存实现渲染卡片, 只依赖数据库中的模板与单位设置
+#[tokio::test]
+async fn in_memory_cards() {
+    setup().await;
+    let telegram_id = 5;
+    let fake = FakeKomari::new(telegram_id);
+
+    let total = total_status(&fake, telegram_id, &NodeFilter::default()).await;
+    assert_contains(&total, &["Mock Komari 总览"]);
+
+    let single = single_server(&fake, telegram_id, 2, NodeTab::Recent).await;
+    assert_contains(&single, &["JP Tokyo", "最近一小时暂无历史记录"]);
+}
@@ -0,0 +1,428 @@
+use crate::ErrorString;
+use crate::connection::api_nodes::get_api_nodes;
+use crate::connection::resolve_node;
+use crate::db::{
+    DB_POOL, MaintenanceWindow, Mute, SuppressedNotification, delete_expired_mutes,
+    delete_maintenance_window, delete_mute, delete_suppressed_notification,
+    insert_maintenance_window, insert_mute, insert_suppressed_notification, query_active_mutes,
+    query_maintenance_windows, query_suppressed_notifications,
+};
+use crate::schedule::{
+    ALL_WEEKDAYS, format_clock, format_timestamp, format_weekdays, in_window, next_clock, now,
+    parse_clock, parse_duration, parse_weekdays,
+};
+use chrono::{DateTime, Duration, FixedOffset};
+use log::{error, info};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt::Write;
+use teloxide::prelude::*;
+use teloxide::types::Recipient;
+
+const SUMMARY_MAX_LINES: usize = 30;
+
+fn is_muted_with(
+    mutes: &[Mute],
+    windows: &[MaintenanceWindow],
+    node_uuid: Option<&str>,
+    time: DateTime<FixedOffset>,
+) -> bool {
+    let applies = |target: &Option<String>| match target {
+        None => true,
+        Some(target) => Some(target.as_str()) == node_uuid,
+    };
+
+    mutes
+        .iter()
+        .any(|mute| applies(&mute.node_uuid) && mute.until_at > time.timestamp())
+        || windows.iter().any(|window| {
+            applies(&window.node_uuid)
+                && in_window(
+                    time,
+                    window.weekdays,
+                    window.start_minute,
+                    window.end_minute,
+                )
+        })
+}
+
+/// Komari 的通知中只包含节点名称, 按最长匹配的名称推断所属节点
+async fn match_notification_node(telegram_id: i64, text: &str) -> Option<String> {
+    let nodes = get_api_nodes(telegram_id).await.ok()?;
+
+    nodes
+        .data
+        .into_iter()
+        .filter(|node| !node.name.is_empty() && text.contains(&node.name))
+        .max_by_key(|node| node.name.len())
+        .map(|node| node.uuid)
+}
+
+/// 若通知所属节点处于静音状态则将其暂存, 返回是否已屏蔽
+pub async fn suppress_if_muted(
+    telegram_id: i64,
+    chat_id: &str,
+    title: &str,
+    message: &str,
+) -> Result<bool, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    let now = now();
+
+    let mutes = query_active_mutes(db_pool, telegram_id, now.timestamp()).await?;
+    let windows = query_maintenance_windows(db_pool, telegram_id).await?;
+
+    if mutes.is_empty() && windows.is_empty() {
+        return Ok(false);
+    }
+
+    let node_uuid = match_notification_node(telegram_id, &format!("{title} {message}")).await;
+
+    if !is_muted_with(&mutes, &windows, node_uuid.as_deref(), now) {
+        return Ok(false);
+    }
+
+    insert_suppressed_notification(
+        db_pool,
+        SuppressedNotification {
+            id: 0,
+            telegram_id,
+            chat_id: chat_id.to_string(),
+            node_uuid,
+            title: title.to_string(),
+            message: message.to_string(),
+            created_at: now.timestamp(),
+        },
+    )
+    .await?;
+
+    Ok(true)
+}
+
+/// 发送 Bot 生成的告警到所有者私聊, 若节点处于静音状态则暂存并在静音结束后汇总
+pub async fn deliver_alert(
+    bot: &Bot,
+    telegram_id: i64,
+    node_uuid: Option<&str>,
+    title: &str,
+    message: &str,
+) -> Result<(), ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    let now = now();
+
+    let mutes = query_active_mutes(db_pool, telegram_id, now.timestamp()).await?;
+    let windows = query_maintenance_windows(db_pool, telegram_id).await?;
+
+    if is_muted_with(&mutes, &windows, node_uuid, now) {
+        info!("告警: {telegram_id} 的告警处于静音中，已暂存");
+        return insert_suppressed_notification(
+            db_pool,
+            SuppressedNotification {
+                id: 0,
+                telegram_id,
+                chat_id: telegram_id.to_string(),
+                node_uuid: node_uuid.map(std::string::ToString::to_string),
+                title: title.to_string(),
+                message: message.to_string(),
+                created_at: now.timestamp(),
+            },
+        )
+        .await;
+    }
+
+    bot.send_message(ChatId(telegram_id), format!("[{title}] {message}"))
+        .await
+        .map_err(|e| format!("无法发送告警: {e}"))?;
+
+    Ok(())
+}
+
+fn chat_recipient(chat_id: &str) -> Recipient {
+    match chat_id.parse::<i64>() {
+        Ok(id) => Recipient::Id(ChatId(id)),
+        Err(_) => Recipient::ChannelUsername(chat_id.to_string()),
+    }
+}
+
+/// 静音结束后将期间屏蔽的通知汇总发送
+async fn flush_suppressed(bot: &Bot) -> Result<(), ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    let now = now();
+
+    let mut mute_cache: HashMap<i64, (Vec<Mute>, Vec<MaintenanceWindow>)> = HashMap::new();
+    let mut summaries: HashMap<(i64, String), Vec<SuppressedNotification>> = HashMap::new();
+
+    for notification in query_suppressed_notifications(db_pool).await? {
+        let (mutes, windows) = match mute_cache.entry(notification.telegram_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mutes =
+                    query_active_mutes(db_pool, notification.telegram_id, now.timestamp()).await?;
+                let windows = query_maintenance_windows(db_pool, notification.telegram_id).await?;
+                entry.insert((mutes, windows))
+            }
+        };
+        if is_muted_with(mutes, windows, notification.node_uuid.as_deref(), now) {
+            continue;
+        }
+
+        summaries
+            .entry((notification.telegram_id, notification.chat_id.clone()))
+            .or_default()
+            .push(notification);
+    }
+
+    for ((telegram_id, chat_id), notifications) in summaries {
+        let mut message = format!("静音已结束，期间共屏蔽 {} 条通知:\n", notifications.len());
+
+        for notification in notifications.iter().take(SUMMARY_MAX_LINES) {
+            let _ = write!(
+                message,
+                "\n{} [{}] {}",
+                format_timestamp(notification.created_at),
+                notification.title,
+                notification.message
+            );
+        }
+
+        if notifications.len() > SUMMARY_MAX_LINES {
+            let _ = write!(
+                message,
+                "\n\n另有 {} 条通知未显示",
+                notifications.len() - SUMMARY_MAX_LINES
+            );
+        }
+
+        if let Err(e) = bot.send_message(chat_recipient(&chat_id), message).await {
+            error!("静音汇总: 无法发送到 {chat_id}: {e}");
+            continue;
+        }
+        info!(
+            "静音汇总: 已向 {chat_id} 发送 {telegram_id} 的 {} 条通知",
+            notifications.len()
+        );
+
+        for notification in notifications {
+            delete_suppressed_notification(db_pool, notification.id).await?;
+        }
+    }
+
+    delete_expired_mutes(db_pool, now.timestamp()).await
+}
+
+pub async fn start_summary_job(bot: Bot) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_mins(1));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = flush_suppressed(&bot).await {
+            error!("静音汇总失败: {e}");
+        }
+    }
+}
+
+async fn resolve_target(
+    telegram_id: i64,
+    target: &str,
+) -> Result<Option<(String, String)>, ErrorString> {
+    if target == "all" {
+        Ok(None)
+    } else {
+        Ok(Some(resolve_node(telegram_id, target).await?))
+    }
+}
+
+fn target_name(node_name: Option<&String>) -> String {
+    node_name.map_or_else(
+        || String::from("所有节点"),
+        std::string::ToString::to_string,
+    )
+}
+
+pub async fn list_mutes(telegram_id: i64) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let mutes = query_active_mutes(db_pool, telegram_id, now().timestamp()).await?;
+    let windows = query_maintenance_windows(db_pool, telegram_id).await?;
+
+    if mutes.is_empty() && windows.is_empty() {
+        return Ok(String::from("当前没有静音或维护窗口"));
+    }
+
+    let mut message = String::new();
+
+    if !mutes.is_empty() {
+        message.push_str("静音中:\n");
+        for mute in mutes {
+            let _ = writeln!(
+                message,
+                "{} - 至 {}",
+                target_name(mute.node_name.as_ref()),
+                format_timestamp(mute.until_at)
+            );
+        }
+    }
+
+    if !windows.is_empty() {
+        message.push_str("\n维护窗口:\n");
+        for window in windows {
+            let _ = writeln!(
+                message,
+                "#{} {} - {}-{} {}",
+                window.id,
+                target_name(window.node_name.as_ref()),
+                format_clock(window.start_minute),
+                format_clock(window.end_minute),
+                format_weekdays(window.weekdays)
+            );
+        }
+    }
+
+    Ok(message)
+}
+
+/// 单次静音的最长时长
+const MAX_MUTE_DAYS: i64 = 365;
+
+/// 静音 `duration` 后的结束时刻, 时长无效或超过上限时返回 `None`
+fn mute_until(now: DateTime<FixedOffset>, duration: &str) -> Option<DateTime<FixedOffset>> {
+    parse_duration(duration)
+        .filter(|duration| *duration <= Duration::days(MAX_MUTE_DAYS))
+        .and_then(|duration| now.checked_add_signed(duration))
+}
+
+/// `/mute NODE|all 2h` 或 `/mute NODE|all until 03:00`, 不带参数时列出生效中的静音
+pub async fn mute(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    if args.is_empty() {
+        return list_mutes(telegram_id).await;
+    }
+
+    let usage = "用法: /mute NODE|all 2h (最长 365d) 或 /mute NODE|all until 03:00";
+
+    let target = args.first().ok_or(usage)?;
+    let now = now();
+
+    let until = match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("until"), Some(clock)) => next_clock(now, parse_clock(clock).ok_or(usage)?),
+        (Some(duration), None) => mute_until(now, duration).ok_or(usage)?,
+        _ => return Err(usage.to_string()),
+    };
+
+    let node = resolve_target(telegram_id, target).await?;
+    let name = target_name(node.as_ref().map(|(_, name)| name));
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    insert_mute(db_pool, telegram_id, node, until.timestamp()).await?;
+
+    Ok(format!(
+        "已静音 {name} 至 {}，期间的通知将在结束后汇总发送",
+        until.format("%Y-%m-%d %H:%M")
+    ))
+}
+
+pub async fn unmute(telegram_id: i64, target: Option<&String>) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let node_uuid = match target.map(String::as_str) {
+        None | Some("all") => None,
+        Some(target) => Some(resolve_node(telegram_id, target).await?.0),
+    };
+
+    let removed = delete_mute(db_pool, telegram_id, node_uuid).await?;
+
+    Ok(format!("已取消 {removed} 个静音"))
+}
+
+/// `/maintenance add NODE|all 02:00-04:00 [daily|weekdays|mon,wed]` 或 `/maintenance del ID`
+pub async fn maintenance(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = "用法:\n/maintenance - 查看维护窗口\n/maintenance add NODE|all 02:00-04:00 [daily|weekdays|mon,wed]\n/maintenance del ID";
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    match args.first().map(String::as_str) {
+        None => list_mutes(telegram_id).await,
+        Some("add") => {
+            let target = args.get(1).ok_or(usage)?;
+            let (start, end) = args
+                .get(2)
+                .and_then(|range| range.split_once('-'))
+                .ok_or(usage)?;
+            let start_minute = parse_clock(start).ok_or(usage)?;
+            let end_minute = parse_clock(end).ok_or(usage)?;
+            let weekdays = match args.get(3) {
+                Some(days) => parse_weekdays(days).ok_or(usage)?,
+                None => ALL_WEEKDAYS,
+            };
+
+            if start_minute == end_minute {
+                return Err(String::from("维护窗口的开始与结束时间不能相同"));
+            }
+
+            let (node_uuid, node_name) = resolve_target(telegram_id, target).await?.unzip();
+            let name = target_name(node_name.as_ref());
+
+            let id = insert_maintenance_window(
+                db_pool,
+                MaintenanceWindow {
+                    id: 0,
+                    telegram_id,
+                    node_uuid,
+                    node_name,
+                    weekdays,
+                    start_minute,
+                    end_minute,
+                },
+            )
+            .await?;
+
+            Ok(format!(
+                "已添加维护窗口 #{id}: {name} {}-{} {}",
+                format_clock(start_minute),
+                format_clock(end_minute),
+                format_weekdays(weekdays)
+            ))
+        }
+        Some("del") => {
+            let id = args
+                .get(1)
+                .and_then(|id| id.trim_start_matches('#').parse::<i64>().ok())
+                .ok_or(usage)?;
+
+            match delete_maintenance_window(db_pool, telegram_id, id).await? {
+                0 => Err(format!("找不到维护窗口 #{id}")),
+                _ => Ok(format!("已删除维护窗口 #{id}")),
+            }
+        }
+        Some(_) => Err(usage.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mute_until_caps_duration() {
+        let now = DateTime::parse_from_rfc3339("2025-01-01T00:00:00+08:00").unwrap();
+
+        assert_eq!(mute_until(now, "2h"), Some(now + Duration::hours(2)));
+        assert_eq!(mute_until(now, "365d"), Some(now + Duration::days(365)));
+        assert_eq!(mute_until(now, "366d"), None);
+        assert_eq!(mute_until(now, "999999999d"), None);
+        assert_eq!(mute_until(now, "999999999999999d"), None);
+    }
+}
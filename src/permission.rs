@@ -0,0 +1,157 @@
+use crate::db::{
+    DB_POOL, delete_chat_permission, query_chat_permission, query_chat_permissions,
+    upsert_chat_permission,
+};
+use crate::{ErrorString, Reply};
+use std::fmt::Write;
+use teloxide::prelude::*;
+
+/// 默认仅群组管理员可执行的命令 (会修改连接、令牌或通知设置)
+pub const MUTATING_COMMANDS: &[&str] = &[
+    "connect",
+    "disconnect",
+    "update",
+    "generate_notification_token",
+    "permission",
+    "mute",
+    "unmute",
+    "maintenance",
+    "digest",
+    "quota",
+    "expiry_remind",
+    "billing",
+    "rate",
+    "import_settings",
+    "latency_alert",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionLevel {
+    Admin,
+    Everyone,
+}
+
+impl PermissionLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "admin" => Some(Self::Admin),
+            "everyone" | "all" => Some(Self::Everyone),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Everyone => "everyone",
+        }
+    }
+
+    pub fn default_for(command: &str) -> Self {
+        if MUTATING_COMMANDS.contains(&command) {
+            Self::Admin
+        } else {
+            Self::Everyone
+        }
+    }
+}
+
+pub async fn command_level(chat_id: i64, command: &str) -> Result<PermissionLevel, ErrorString> {
+    // permission 命令本身不可被覆盖，防止普通成员提权
+    if command == "permission" {
+        return Ok(PermissionLevel::Admin);
+    }
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    Ok(query_chat_permission(db_pool, chat_id, command)
+        .await?
+        .and_then(|level| PermissionLevel::parse(&level))
+        .unwrap_or_else(|| PermissionLevel::default_for(command)))
+}
+
+pub async fn is_chat_admin(bot: &Bot, msg: &Message) -> Result<bool, ErrorString> {
+    // 匿名管理员以群组身份发言
+    if msg.sender_chat.as_ref().map(|chat| chat.id) == Some(msg.chat.id) {
+        return Ok(true);
+    }
+
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+
+    let member = bot
+        .get_chat_member(msg.chat.id, user.id)
+        .await
+        .map_err(|e| format!("无法获取群组成员信息: {e}"))?;
+
+    Ok(member.is_privileged())
+}
+
+pub async fn check_permission(
+    bot: &Bot,
+    msg: &Message,
+    command: &str,
+) -> Result<bool, ErrorString> {
+    if msg.chat.is_private() {
+        return Ok(true);
+    }
+
+    match command_level(msg.chat.id.0, command).await? {
+        PermissionLevel::Everyone => Ok(true),
+        PermissionLevel::Admin => is_chat_admin(bot, msg).await,
+    }
+}
+
+/// `/permission [COMMAND admin|everyone|default]`, 不带参数时列出本群的覆盖设置
+pub async fn command(
+    msg: &Message,
+    command: Option<String>,
+    level: Option<String>,
+    names: &[&str],
+) -> Reply {
+    if msg.chat.is_private() {
+        return Reply::Text(String::from("此命令只能用于群组"));
+    }
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+    let chat_id = msg.chat.id.0;
+
+    let (Some(command), Some(level)) = (command, level) else {
+        return Reply::Text(match query_chat_permissions(db_pool, chat_id).await {
+            Ok(overrides) if overrides.is_empty() => {
+                String::from("本群未设置权限覆盖，修改类命令默认仅限管理员使用")
+            }
+            Ok(overrides) => {
+                let mut message = String::from("本群命令权限覆盖:\n");
+                for (command, level) in overrides {
+                    let _ = writeln!(message, "/{command} - {level}");
+                }
+                message
+            }
+            Err(e) => format!("无法获取权限设置: {e}"),
+        });
+    };
+
+    let command = command.trim_start_matches('/').to_string();
+    if !names.contains(&command.as_str()) || command == "permission" {
+        return Reply::Text(format!("无法设置该命令的权限: {command}"));
+    }
+
+    let result = if level == "default" {
+        delete_chat_permission(db_pool, chat_id, &command).await
+    } else if let Some(level) = PermissionLevel::parse(&level) {
+        upsert_chat_permission(db_pool, chat_id, &command, level.as_str()).await
+    } else {
+        return Reply::Text(String::from("权限等级只能为 admin, everyone 或 default"));
+    };
+
+    Reply::Text(match result {
+        Ok(()) => format!("已更新 /{command} 的权限为 {level}"),
+        Err(e) => format!("更新权限失败: {e}"),
+    })
+}
@@ -0,0 +1,489 @@
+use crate::ErrorString;
+use crate::connection::resolve_node;
+use crate::connection::ws_get::{ApiWs, get_ws};
+use crate::db::{
+    DB_POOL, TrafficQuota, delete_traffic_quota, query_traffic_quotas, upsert_traffic_quota,
+};
+use crate::markup::Markup;
+use crate::mute::deliver_alert;
+use crate::schedule::{format_timestamp, local_offset, now};
+use crate::units::{load_units, parse_size};
+use chrono::{DateTime, Datelike, FixedOffset, Months, NaiveDate, TimeZone};
+use log::error;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use teloxide::prelude::*;
+
+const ALERT_THRESHOLDS: [i64; 3] = [100, 90, 80];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Sum,
+    Max,
+}
+
+impl Direction {
+    pub fn parse(direction: &str) -> Option<Self> {
+        match direction {
+            "up" => Some(Self::Up),
+            "down" => Some(Self::Down),
+            "sum" => Some(Self::Sum),
+            "max" => Some(Self::Max),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Sum => "sum",
+            Self::Max => "max",
+        }
+    }
+
+    pub fn used(self, up: u64, down: u64) -> u64 {
+        match self {
+            Self::Up => up,
+            Self::Down => down,
+            Self::Sum => up + down,
+            Self::Max => up.max(down),
+        }
+    }
+}
+
+fn quota_used(quota: &TrafficQuota) -> u64 {
+    Direction::parse(&quota.direction)
+        .unwrap_or(Direction::Sum)
+        .used(quota.used_up as u64, quota.used_down as u64)
+}
+
+/// 某月的重置日, 月份天数不足时取当月最后一天
+fn reset_date(year: i32, month: u32, reset_day: i64) -> Option<NaiveDate> {
+    (1..=u32::try_from(reset_day).ok()?)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+/// 当前计费周期的开始与结束时间
+pub fn cycle_bounds(
+    time: DateTime<FixedOffset>,
+    reset_day: i64,
+) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let today = time.date_naive();
+
+    let mut start = reset_date(today.year(), today.month(), reset_day)?;
+    if start > today {
+        let previous = today.with_day(1)? - Months::new(1);
+        start = reset_date(previous.year(), previous.month(), reset_day)?;
+    }
+
+    let next = start.with_day(1)? + Months::new(1);
+    let end = reset_date(next.year(), next.month(), reset_day)?;
+
+    let offset = local_offset();
+    Some((
+        offset
+            .from_local_datetime(&start.and_hms_opt(0, 0, 0)?)
+            .single()?,
+        offset
+            .from_local_datetime(&end.and_hms_opt(0, 0, 0)?)
+            .single()?,
+    ))
+}
+
+/// 根据新的 WebSocket 数据累加流量, 计数器变小时视为节点重启并以当前值作为增量
+///
+/// `cycle_start` 为本周期开始统计的时间: 持续采样时为周期开始, 周期中途设置配额时为首次采样
+fn accumulate(quota: &mut TrafficQuota, ws_data: &ApiWs, time: DateTime<FixedOffset>) {
+    if let Some((start, _)) = cycle_bounds(time, quota.reset_day)
+        && quota.cycle_start < start.timestamp()
+    {
+        quota.cycle_start = if quota.last_raw_up.is_some() {
+            start.timestamp()
+        } else {
+            time.timestamp()
+        };
+        quota.used_up = 0;
+        quota.used_down = 0;
+        quota.alerted_percent = 0;
+    }
+
+    let Some(data) = ws_data.data.data.get(&quota.node_uuid) else {
+        return;
+    };
+
+    let raw_up = data.network.total_up as i64;
+    let raw_down = data.network.total_down as i64;
+
+    if let (Some(last_up), Some(last_down)) = (quota.last_raw_up, quota.last_raw_down) {
+        quota.used_up += if raw_up >= last_up {
+            raw_up - last_up
+        } else {
+            raw_up
+        };
+        quota.used_down += if raw_down >= last_down {
+            raw_down - last_down
+        } else {
+            raw_down
+        };
+    }
+
+    quota.last_raw_up = Some(raw_up);
+    quota.last_raw_down = Some(raw_down);
+}
+
+/// 采样该用户所有配额节点, 返回需要发送告警的配额及其阈值
+pub async fn sample(telegram_id: i64) -> Result<Vec<(TrafficQuota, i64)>, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let quotas = query_traffic_quotas(db_pool, Some(telegram_id)).await?;
+    if quotas.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let ws_data = get_ws(telegram_id).await?;
+    let now = now();
+    let mut alerts = vec![];
+
+    for mut quota in quotas {
+        accumulate(&mut quota, &ws_data, now);
+
+        let percent = quota_used(&quota) as f64 / quota.limit_bytes as f64 * 100.0;
+        if let Some(threshold) = ALERT_THRESHOLDS
+            .into_iter()
+            .find(|threshold| percent >= *threshold as f64)
+            && threshold > quota.alerted_percent
+        {
+            quota.alerted_percent = threshold;
+            alerts.push((quota.clone(), threshold));
+        }
+
+        upsert_traffic_quota(db_pool, quota).await?;
+    }
+
+    Ok(alerts)
+}
+
+pub async fn send_alerts(bot: &Bot, alerts: Vec<(TrafficQuota, i64)>) {
+    for (quota, threshold) in alerts {
+        let units = load_units(quota.telegram_id).await.unwrap_or_default();
+        let message = format!(
+            "{} 本周期流量已使用 {threshold}%: {} / {}",
+            quota.node_name,
+            units.bytes(quota_used(&quota)),
+            units.bytes(quota.limit_bytes as u64)
+        );
+
+        if let Err(e) = deliver_alert(
+            bot,
+            quota.telegram_id,
+            Some(&quota.node_uuid),
+            "流量告警",
+            &message,
+        )
+        .await
+        {
+            error!("流量告警: {e}");
+        }
+    }
+}
+
+pub async fn start_quota_job(bot: Bot) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_mins(5));
+
+    loop {
+        interval.tick().await;
+
+        let db_pool = DB_POOL
+            .get()
+            .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+        let telegram_ids = match query_traffic_quotas(db_pool, None).await {
+            Ok(quotas) => quotas
+                .into_iter()
+                .map(|quota| quota.telegram_id)
+                .collect::<BTreeSet<_>>(),
+            Err(e) => {
+                error!("流量统计: {e}");
+                continue;
+            }
+        };
+
+        for telegram_id in telegram_ids {
+            match sample(telegram_id).await {
+                Ok(alerts) => send_alerts(&bot, alerts).await,
+                Err(e) => error!("流量统计: 无法采样 {telegram_id}: {e}"),
+            }
+        }
+    }
+}
+
+/// 配额上限, 须为正数且能存入数据库
+fn parse_limit(text: &str) -> Option<i64> {
+    parse_size(text)
+        .and_then(|limit| i64::try_from(limit).ok())
+        .filter(|limit| *limit > 0)
+}
+
+/// `/quota NODE LIMIT [RESET_DAY] [up|down|sum|max]` 或 `/quota NODE off`
+pub async fn quota(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = "用法:\n/quota - 查看流量配额\n/quota NODE_ID 1T [重置日 1-31] [up|down|sum|max]\n/quota NODE_ID off";
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let units = load_units(telegram_id).await?;
+
+    let Some(node) = args.first() else {
+        let quotas = query_traffic_quotas(db_pool, Some(telegram_id)).await?;
+        if quotas.is_empty() {
+            return Ok(format!("未设置流量配额\n\n{usage}"));
+        }
+
+        let mut message = String::from("流量配额:\n");
+        for quota in quotas {
+            let _ = writeln!(
+                message,
+                "{} - {} 每月 {} 日重置 ({})",
+                quota.node_name,
+                units.bytes(quota.limit_bytes as u64),
+                quota.reset_day,
+                quota.direction
+            );
+        }
+        return Ok(message);
+    };
+
+    let (node_uuid, node_name) = resolve_node(telegram_id, node).await?;
+
+    let limit = args.get(1).ok_or(usage)?;
+    if limit == "off" {
+        return match delete_traffic_quota(db_pool, telegram_id, &node_uuid).await? {
+            0 => Err(format!("{node_name} 未设置流量配额")),
+            _ => Ok(format!("已删除 {node_name} 的流量配额")),
+        };
+    }
+
+    let limit_bytes = parse_limit(limit).ok_or(usage)?;
+    let reset_day = match args.get(2) {
+        Some(day) => day
+            .parse::<i64>()
+            .ok()
+            .filter(|day| (1..=31).contains(day))
+            .ok_or(usage)?,
+        None => 1,
+    };
+    let direction = match args.get(3) {
+        Some(direction) => Direction::parse(direction).ok_or(usage)?,
+        None => Direction::Sum,
+    };
+
+    let existing = query_traffic_quotas(db_pool, Some(telegram_id))
+        .await?
+        .into_iter()
+        .find(|quota| quota.node_uuid == node_uuid);
+
+    let updating = existing.is_some();
+
+    // 修改配额时保留已累计的用量
+    let quota = match existing {
+        Some(existing) => TrafficQuota {
+            node_name: node_name.clone(),
+            limit_bytes,
+            reset_day,
+            direction: direction.as_str().to_string(),
+            alerted_percent: 0,
+            ..existing
+        },
+        None => TrafficQuota {
+            telegram_id,
+            node_uuid,
+            node_name: node_name.clone(),
+            limit_bytes,
+            reset_day,
+            direction: direction.as_str().to_string(),
+            cycle_start: 0,
+            used_up: 0,
+            used_down: 0,
+            last_raw_up: None,
+            last_raw_down: None,
+            alerted_percent: 0,
+        },
+    };
+
+    let used = quota_used(&quota);
+    upsert_traffic_quota(db_pool, quota).await?;
+
+    let progress = if updating {
+        format!("本周期已用 {}", units.bytes(used))
+    } else {
+        String::from("用量从现在开始统计")
+    };
+
+    Ok(format!(
+        "已设置 {node_name} 的流量配额: {} 每月 {reset_day} 日重置 ({})\n{progress}",
+        units.bytes(limit_bytes.unsigned_abs()),
+        direction.as_str()
+    ))
+}
+
+/// `/traffic` 的消息, 以最新数据计算用量但不保存也不发送告警, 由后台任务负责
+pub async fn traffic(telegram_id: i64) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let mut quotas = query_traffic_quotas(db_pool, Some(telegram_id)).await?;
+    if quotas.is_empty() {
+        return Err(String::from("未设置流量配额，请先使用 /quota 设置"));
+    }
+
+    let units = load_units(telegram_id).await?;
+    let now = now();
+
+    // 无法获取实时数据时显示上次采样的结果
+    if let Ok(ws_data) = get_ws(telegram_id).await {
+        for quota in &mut quotas {
+            accumulate(quota, &ws_data, now);
+        }
+    }
+
+    let mut message = Markup::default();
+
+    for (index, quota) in quotas.iter().enumerate() {
+        let used = quota_used(quota);
+        let percent = used as f64 / quota.limit_bytes as f64 * 100.0;
+
+        if index > 0 {
+            message.line();
+        }
+        message
+            .text(&quota.node_name)
+            .line()
+            .text("用量: ")
+            .code(units.bytes(used))
+            .text(" / ")
+            .code(units.bytes(quota.limit_bytes as u64))
+            .text(" ")
+            .code(units.percent(percent))
+            .text(format!(" ({})", quota.direction))
+            .line();
+
+        if let Some((start, end)) = cycle_bounds(now, quota.reset_day)
+            && quota.cycle_start > 0
+        {
+            // 按开始统计以来的速率推算, 周期中途设置的配额不会被低估
+            let counted_since = quota.cycle_start.max(start.timestamp());
+            let elapsed = (now.timestamp() - counted_since).max(1);
+            let total = end.timestamp() - counted_since;
+            let projected = (used as f64 / elapsed as f64 * total as f64) as u64;
+
+            message
+                .text("预计周期末: ")
+                .code(units.bytes(projected))
+                .text(" ")
+                .code(units.percent(projected as f64 / quota.limit_bytes as f64 * 100.0))
+                .line()
+                .field("重置时间", format_timestamp(end.timestamp()));
+        }
+    }
+
+    Ok(message.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const NODE: &str = "0a4c6f1e-2b3d-4e5f-8a9b-0c1d2e3f4a5b";
+
+    fn ws_data(total_up: u64) -> ApiWs {
+        let mut ws_data: ApiWs =
+            serde_json::from_str(include_str!("../fixtures/komari/clients.json")).unwrap();
+        if let Some(data) = ws_data.data.data.get_mut(NODE) {
+            data.network.total_up = total_up;
+        }
+        ws_data
+    }
+
+    fn new_quota() -> TrafficQuota {
+        TrafficQuota {
+            telegram_id: 1,
+            node_uuid: NODE.to_string(),
+            node_name: String::from("HK-Web_01"),
+            limit_bytes: 1 << 40,
+            reset_day: 1,
+            direction: String::from("up"),
+            cycle_start: 0,
+            used_up: 0,
+            used_down: 0,
+            last_raw_up: None,
+            last_raw_down: None,
+            alerted_percent: 0,
+        }
+    }
+
+    fn time(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn limit_rejects_sizes_out_of_range() {
+        assert_eq!(parse_limit("1T"), Some(1 << 40));
+        assert_eq!(parse_limit("0"), None);
+        assert_eq!(parse_limit("20000P"), None);
+        assert_eq!(parse_limit("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn quota_set_mid_cycle_counts_from_first_sample() {
+        let mut quota = new_quota();
+        let first = time("2026-10-15T12:00:00+00:00");
+
+        accumulate(&mut quota, &ws_data(1_000), first);
+        assert_eq!(quota.cycle_start, first.timestamp());
+        assert_eq!(quota.used_up, 0);
+
+        accumulate(&mut quota, &ws_data(1_500), first + Duration::minutes(5));
+        assert_eq!(quota.cycle_start, first.timestamp());
+        assert_eq!(quota.used_up, 500);
+    }
+
+    #[test]
+    fn continuous_sampling_resets_at_cycle_start() {
+        let mut quota = new_quota();
+        accumulate(
+            &mut quota,
+            &ws_data(1_000),
+            time("2026-10-31T23:58:00+00:00"),
+        );
+        accumulate(
+            &mut quota,
+            &ws_data(1_200),
+            time("2026-11-01T00:03:00+00:00"),
+        );
+
+        assert_eq!(
+            quota.cycle_start,
+            time("2026-11-01T00:00:00+00:00").timestamp()
+        );
+        assert_eq!(quota.used_up, 200);
+    }
+
+    #[test]
+    fn counter_reset_counts_current_value() {
+        let mut quota = new_quota();
+        let first = time("2026-10-15T12:00:00+00:00");
+        accumulate(&mut quota, &ws_data(1_000), first);
+        accumulate(&mut quota, &ws_data(300), first + Duration::minutes(5));
+
+        assert_eq!(quota.used_up, 300);
+    }
+}
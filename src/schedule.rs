@@ -0,0 +1,230 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveTime, TimeZone, Timelike, Utc};
+use std::env;
+
+const WEEKDAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const WEEKDAY_FULL_NAMES: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+pub const ALL_WEEKDAYS: i64 = 0b111_1111;
+
+/// 读取配置中的时区, 格式为 `+08:00`, 默认为 UTC
+pub fn local_offset() -> FixedOffset {
+    env::var("TIMEZONE")
+        .ok()
+        .and_then(|timezone| parse_offset(&timezone))
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+}
+
+pub fn parse_offset(timezone: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match timezone.chars().next()? {
+        '+' => (1, &timezone[1..]),
+        '-' => (-1, &timezone[1..]),
+        _ => (1, timezone),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+
+    FixedOffset::east_opt(sign * seconds)
+}
+
+pub fn now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&local_offset())
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    match local_offset().timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// 解析 `30m`, `2h`, `1d`, `1h30m` 形式的时长, 超出范围时返回 `None`
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut number = String::new();
+
+    for char in text.chars() {
+        if char.is_ascii_digit() {
+            number.push(char);
+            continue;
+        }
+
+        let value = number.parse::<i64>().ok()?;
+        number.clear();
+
+        let part = match char {
+            's' => Duration::try_seconds(value),
+            'm' => Duration::try_minutes(value),
+            'h' => Duration::try_hours(value),
+            'd' => Duration::try_days(value),
+            'w' => Duration::try_weeks(value),
+            _ => return None,
+        };
+        total = total.checked_add(&part?)?;
+    }
+
+    if !number.is_empty() || total <= Duration::zero() {
+        return None;
+    }
+
+    Some(total)
+}
+
+/// 解析 `03:00`, 返回当天零点起的分钟数
+pub fn parse_clock(text: &str) -> Option<i64> {
+    let time = NaiveTime::parse_from_str(text, "%H:%M").ok()?;
+    Some(i64::from(time.hour() * 60 + time.minute()))
+}
+
+pub fn format_clock(minute: i64) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// 下一次到达本地时间 `minute` 的时刻
+pub fn next_clock(from: DateTime<FixedOffset>, minute: i64) -> DateTime<FixedOffset> {
+    let midnight = from - Duration::seconds(i64::from(from.num_seconds_from_midnight()));
+    let target = midnight + Duration::minutes(minute);
+
+    if target > from {
+        target
+    } else {
+        target + Duration::days(1)
+    }
+}
+
+pub fn minute_of_day(time: DateTime<FixedOffset>) -> i64 {
+    i64::from(time.num_seconds_from_midnight()) / 60
+}
+
+pub fn weekday_bit(time: DateTime<FixedOffset>) -> i64 {
+    1 << time.weekday().num_days_from_monday()
+}
+
+/// 下一次到达本地时间 `weekday` (周一为 0) `minute` 的时刻
+pub fn next_weekly(
+    from: DateTime<FixedOffset>,
+    weekday: i64,
+    minute: i64,
+) -> DateTime<FixedOffset> {
+    let mut target = next_clock(from, minute);
+
+    while i64::from(target.weekday().num_days_from_monday()) != weekday {
+        target += Duration::days(1);
+    }
+
+    target
+}
+
+/// 星期的三字母缩写或全称, 不区分大小写
+pub fn parse_weekday(text: &str) -> Option<i64> {
+    let text = text.trim().to_lowercase();
+    WEEKDAY_NAMES
+        .iter()
+        .zip(WEEKDAY_FULL_NAMES)
+        .position(|(short, full)| text == *short || text == full)
+        .and_then(|index| i64::try_from(index).ok())
+}
+
+pub fn weekday_name(weekday: i64) -> &'static str {
+    WEEKDAY_NAMES
+        .get(usize::try_from(weekday).unwrap_or_default())
+        .copied()
+        .unwrap_or("mon")
+}
+
+/// 解析 Komari 返回的 RFC3339 时间, 忽略 Go 零值 (`0001-01-01`)
+pub fn parse_komari_time(text: &str) -> Option<DateTime<FixedOffset>> {
+    let time = DateTime::parse_from_rfc3339(text).ok()?;
+
+    if time.year() < 2000 {
+        return None;
+    }
+
+    Some(time.with_timezone(&local_offset()))
+}
+
+/// 解析 `daily`, `weekdays`, `weekends` 或 `mon,wed,fri`
+pub fn parse_weekdays(text: &str) -> Option<i64> {
+    match text {
+        "daily" | "everyday" => return Some(ALL_WEEKDAYS),
+        "weekdays" => return Some(0b001_1111),
+        "weekends" => return Some(0b110_0000),
+        _ => {}
+    }
+
+    let mut weekdays = 0;
+    for day in text.split(',') {
+        weekdays |= 1 << parse_weekday(day)?;
+    }
+
+    Some(weekdays)
+}
+
+pub fn format_weekdays(weekdays: i64) -> String {
+    if weekdays == ALL_WEEKDAYS {
+        return String::from("daily");
+    }
+
+    WEEKDAY_NAMES
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| weekdays & (1 << index) != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 判断 `time` 是否处于每周重复的时间窗口内, 支持跨越零点的窗口
+pub fn in_window(time: DateTime<FixedOffset>, weekdays: i64, start: i64, end: i64) -> bool {
+    let minute = minute_of_day(time);
+    let today = weekday_bit(time) & weekdays != 0;
+    let yesterday = weekday_bit(time - Duration::days(1)) & weekdays != 0;
+
+    if start <= end {
+        today && minute >= start && minute < end
+    } else {
+        (today && minute >= start) || (yesterday && minute < end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_weekday_accepts_short_and_full_names() {
+        assert_eq!(parse_weekday("mon"), Some(0));
+        assert_eq!(parse_weekday("Sunday"), Some(6));
+        assert_eq!(parse_weekday(" FRI "), Some(4));
+    }
+
+    #[test]
+    fn parse_weekday_rejects_prefixed_words() {
+        assert_eq!(parse_weekday("monkey"), None);
+        assert_eq!(parse_weekday("sundays"), None);
+        assert_eq!(parse_weekday("mo"), None);
+        assert_eq!(parse_weekdays("mon,satx"), None);
+    }
+
+    #[test]
+    fn parse_duration_sums_units() {
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("2d"), Some(Duration::days(2)));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("5"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_huge_values() {
+        assert_eq!(parse_duration("999999999999999d"), None);
+        assert_eq!(parse_duration("9999999999999w"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+        assert_eq!(parse_duration("9223372036854775s9223372036854775s"), None);
+    }
+}
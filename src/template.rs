@@ -0,0 +1,376 @@
+use crate::ErrorString;
+use crate::connection::ws_get::status::format_duration;
+use crate::db::{DB_POOL, delete_card_template, query_card_template, upsert_card_template};
+use crate::markup::Markup;
+use crate::units::Units;
+use std::collections::HashMap;
+
+/// 自定义模板的长度上限, 避免渲染结果超过 Telegram 的 4096 字符
+const MAX_TEMPLATE_LEN: usize = 1500;
+
+const DEFAULT_STATUS_TEMPLATE: &str = "UPTIME: {uptime}
+
+CPU: {cpu}
+RAM: {ram_used} / {ram_total} {ram_percent}
+SWAP: {swap_used} / {swap_total} {swap_percent}
+DISK: {disk_used} / {disk_total} {disk_percent}
+
+LOAD: {load1} / {load5} / {load15}
+PROC: {process}";
+
+const DEFAULT_OVERVIEW_TEMPLATE: &str = "ONLINE: {online} / {total} {online_percent}
+CPU CORES: {cores}
+AVG CPU: {cpu}
+AVG LOAD: {load1} / {load5} / {load15}
+
+MEM: {ram_used} / {ram_total} {ram_percent}
+SWAP: {swap_used} / {swap_total} {swap_percent}
+DISK: {disk_used} / {disk_total} {disk_percent}
+
+DOWN: {net_total_down}
+UP: {net_total_up}
+DOWN SPEED: {net_down}
+UP SPEED: {net_up}
+CONN: {tcp} TCP / {udp} UDP";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Text,
+    Count,
+    Number,
+    Percent,
+    Bytes,
+    Rate,
+    Duration,
+}
+
+impl FieldType {
+    fn units(self) -> &'static [&'static str] {
+        match self {
+            Self::Bytes => &["B", "KB", "MB", "GB", "TB"],
+            Self::Rate => &["Kbps", "Mbps", "Gbps", "KB/s", "MB/s"],
+            _ => &[],
+        }
+    }
+}
+
+const STATUS_FIELDS: &[(&str, FieldType)] = &[
+    ("name", FieldType::Text),
+    ("region", FieldType::Text),
+    ("os", FieldType::Text),
+    ("arch", FieldType::Text),
+    ("virt", FieldType::Text),
+    ("kernel", FieldType::Text),
+    ("cpu_name", FieldType::Text),
+    ("gpu", FieldType::Text),
+    ("cores", FieldType::Count),
+    ("uptime", FieldType::Duration),
+    ("cpu", FieldType::Percent),
+    ("ram_used", FieldType::Bytes),
+    ("ram_total", FieldType::Bytes),
+    ("ram_percent", FieldType::Percent),
+    ("swap_used", FieldType::Bytes),
+    ("swap_total", FieldType::Bytes),
+    ("swap_percent", FieldType::Percent),
+    ("disk_used", FieldType::Bytes),
+    ("disk_total", FieldType::Bytes),
+    ("disk_percent", FieldType::Percent),
+    ("load1", FieldType::Number),
+    ("load5", FieldType::Number),
+    ("load15", FieldType::Number),
+    ("process", FieldType::Count),
+    ("net_up", FieldType::Rate),
+    ("net_down", FieldType::Rate),
+    ("net_total_up", FieldType::Bytes),
+    ("net_total_down", FieldType::Bytes),
+    ("tcp", FieldType::Count),
+    ("udp", FieldType::Count),
+];
+
+const OVERVIEW_FIELDS: &[(&str, FieldType)] = &[
+    ("online", FieldType::Count),
+    ("total", FieldType::Count),
+    ("online_percent", FieldType::Percent),
+    ("cores", FieldType::Count),
+    ("cpu", FieldType::Percent),
+    ("load1", FieldType::Number),
+    ("load5", FieldType::Number),
+    ("load15", FieldType::Number),
+    ("ram_used", FieldType::Bytes),
+    ("ram_total", FieldType::Bytes),
+    ("ram_percent", FieldType::Percent),
+    ("swap_used", FieldType::Bytes),
+    ("swap_total", FieldType::Bytes),
+    ("swap_percent", FieldType::Percent),
+    ("disk_used", FieldType::Bytes),
+    ("disk_total", FieldType::Bytes),
+    ("disk_percent", FieldType::Percent),
+    ("net_up", FieldType::Rate),
+    ("net_down", FieldType::Rate),
+    ("net_total_up", FieldType::Bytes),
+    ("net_total_down", FieldType::Bytes),
+    ("tcp", FieldType::Count),
+    ("udp", FieldType::Count),
+];
+
+/// `status` 为节点卡片的概览标签页, `overview` 为 `/total_status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKind {
+    Status,
+    Overview,
+}
+
+impl TemplateKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "status" => Some(Self::Status),
+            "overview" | "total_status" => Some(Self::Overview),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Status => "status",
+            Self::Overview => "overview",
+        }
+    }
+
+    pub fn default_template(self) -> &'static str {
+        match self {
+            Self::Status => DEFAULT_STATUS_TEMPLATE,
+            Self::Overview => DEFAULT_OVERVIEW_TEMPLATE,
+        }
+    }
+
+    fn fields(self) -> &'static [(&'static str, FieldType)] {
+        match self {
+            Self::Status => STATUS_FIELDS,
+            Self::Overview => OVERVIEW_FIELDS,
+        }
+    }
+
+    /// 字段列表, 带单位的字段附上可选单位
+    pub fn describe_fields(self) -> String {
+        self.fields()
+            .iter()
+            .map(|(name, field_type)| match field_type.units() {
+                [] => (*name).to_string(),
+                units => format!("{name}|{}", units.join("/")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// 模板中字段的取值, 字节与速率以 B 与 B/s 为单位
+#[derive(Debug, Clone)]
+pub enum TemplateValue {
+    Text(String),
+    Count(u64),
+    Number(f64),
+    Percent(f64),
+    Bytes(u64),
+    Rate(u64),
+    Duration(u64),
+}
+
+impl TemplateValue {
+    /// 未指定单位时按用户的单位偏好自动换算, 指定的单位按偏好的进制换算
+    fn format(&self, unit: Option<&str>, units: &Units) -> String {
+        match self {
+            Self::Text(text) if text.is_empty() => String::from("-"),
+            Self::Text(text) => text.clone(),
+            Self::Count(count) => count.to_string(),
+            Self::Number(number) => units.number(*number),
+            Self::Percent(percent) => units.percent(*percent),
+            Self::Bytes(bytes) => {
+                let power = FieldType::Bytes
+                    .units()
+                    .iter()
+                    .position(|bytes_unit| Some(*bytes_unit) == unit);
+                match power {
+                    Some(power) => units.bytes_in(*bytes, power),
+                    None => units.bytes(*bytes),
+                }
+            }
+            Self::Rate(rate) => {
+                let bits = *rate as f64 * 8.0;
+                match unit {
+                    Some(unit @ "Kbps") => format!("{} {unit}", units.number(bits / 1e3)),
+                    Some(unit @ "Mbps") => format!("{} {unit}", units.number(bits / 1e6)),
+                    Some(unit @ "Gbps") => format!("{} {unit}", units.number(bits / 1e9)),
+                    Some("KB/s") => format!("{}/s", units.bytes_in(*rate, 1)),
+                    Some("MB/s") => format!("{}/s", units.bytes_in(*rate, 2)),
+                    _ => units.rate(*rate as f64),
+                }
+            }
+            Self::Duration(seconds) => format_duration(*seconds),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field { name: String, unit: Option<String> },
+}
+
+/// 由文字与 `{字段}` / `{字段|单位}` 组成的模板, `{{` 与 `}}` 表示花括号本身
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(kind: TemplateKind, text: &str) -> Result<Self, ErrorString> {
+        if text.chars().count() > MAX_TEMPLATE_LEN {
+            return Err(format!("模板过长，最多 {MAX_TEMPLATE_LEN} 个字符"));
+        }
+
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            match char {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(char) => placeholder.push(char),
+                            None => return Err(String::from("模板中有未闭合的 {")),
+                        }
+                    }
+
+                    let (name, unit) = match placeholder.split_once('|') {
+                        Some((name, unit)) => (name.trim(), Some(unit.trim())),
+                        None => (placeholder.trim(), None),
+                    };
+                    let field_type = kind
+                        .fields()
+                        .iter()
+                        .find(|(field, _)| *field == name)
+                        .map(|(_, field_type)| *field_type)
+                        .ok_or(format!("未知的字段: {name}"))?;
+                    if let Some(unit) = unit
+                        && !field_type.units().contains(&unit)
+                    {
+                        return Err(format!("字段 {name} 不支持单位 {unit}"));
+                    }
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field {
+                        name: name.to_string(),
+                        unit: unit.map(str::to_string),
+                    });
+                }
+                '}' => return Err(String::from("模板中有多余的 }，请使用 }} 表示花括号")),
+                _ => literal.push(char),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if !segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field { .. }))
+        {
+            return Err(String::from("模板中至少需要一个字段"));
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn default_for(kind: TemplateKind) -> Self {
+        Self::parse(kind, kind.default_template()).unwrap_or_else(|e| panic!("内置模板无效: {e}"))
+    }
+
+    /// 文字与字段值分别写入消息, 字段值以等宽格式显示
+    pub fn render(
+        &self,
+        values: &HashMap<&str, TemplateValue>,
+        units: &Units,
+        message: &mut Markup,
+    ) {
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => message.text(text),
+                Segment::Field { name, unit } => {
+                    message.code(values.get(name.as_str()).map_or_else(
+                        || String::from("-"),
+                        |value| value.format(unit.as_deref(), units),
+                    ))
+                }
+            };
+        }
+    }
+}
+
+/// 用户保存的模板, 未设置或已失效时使用内置模板
+pub async fn load_template(telegram_id: i64, kind: TemplateKind) -> Result<Template, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let template = query_card_template(db_pool, telegram_id, kind.as_str()).await?;
+
+    Ok(template
+        .and_then(|template| Template::parse(kind, &template).ok())
+        .unwrap_or_else(|| Template::default_for(kind)))
+}
+
+/// `/template [status|overview] [模板|reset]`
+pub async fn template(
+    telegram_id: i64,
+    kind: Option<&str>,
+    body: Option<&str>,
+) -> Result<String, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let Some(kind) = kind else {
+        return Ok(String::from(
+            "用法:\n/template status|overview - 查看当前模板与可用字段\n/template status|overview 模板 - 设置模板, 可换行\n/template status|overview reset - 恢复内置模板\n\nstatus 为节点卡片的概览页, overview 为 /total_status\n字段写作 {字段} 或 {字段|单位}, 例如 RAM: {ram_used|GB} / {ram_total|GB}\n未指定单位时按 /units 的设置自动换算",
+        ));
+    };
+    let kind = TemplateKind::parse(kind).ok_or(format!("未知的模板类型: {kind}"))?;
+
+    match body {
+        None => {
+            let template = query_card_template(db_pool, telegram_id, kind.as_str()).await?;
+            let (source, template) = match &template {
+                Some(template) => ("自定义", template.as_str()),
+                None => ("内置", kind.default_template()),
+            };
+
+            Ok(format!(
+                "当前 {} 模板 ({source}):\n\n{template}\n\n可用字段:\n{}",
+                kind.as_str(),
+                kind.describe_fields()
+            ))
+        }
+        Some("reset") => {
+            delete_card_template(db_pool, telegram_id, kind.as_str()).await?;
+            Ok(format!("已恢复内置 {} 模板", kind.as_str()))
+        }
+        Some(body) => {
+            Template::parse(kind, body)?;
+            upsert_card_template(db_pool, telegram_id, kind.as_str(), body).await?;
+            Ok(format!("已保存 {} 模板", kind.as_str()))
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::ErrorString;
+use crate::db::{DB_POOL, delete_unit_setting, query_unit_setting, upsert_unit_setting};
+
+/// 容量单位对应的 1024 的幂, 例如 `G`, `GB` 与 `GiB` 均为 3
+pub fn size_power(unit: &str) -> Option<i32> {
+    match unit
+        .trim()
+        .to_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => Some(0),
+        "K" => Some(1),
+        "M" => Some(2),
+        "G" => Some(3),
+        "T" => Some(4),
+        "P" => Some(5),
+        _ => None,
+    }
+}
+
+/// 解析 `500G`, `1.5T`, `2GB`, `1024` 形式的容量, 按 1024 进制换算为字节
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_uppercase();
+    let split = text
+        .find(|char: char| !char.is_ascii_digit() && char != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+
+    let power = size_power(unit)?;
+
+    if number < 0.0 {
+        return None;
+    }
+
+    Some((number * 1024f64.powi(power)) as u64)
+}
+
+/// 小数位数上限
+const MAX_PRECISION: usize = 4;
+
+/// 容量进制, IEC 以 1024 换算 (KiB), SI 以 1000 换算 (KB)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeBase {
+    Iec,
+    Si,
+}
+
+impl SizeBase {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.to_lowercase().as_str() {
+            "iec" | "1024" => Some(Self::Iec),
+            "si" | "1000" => Some(Self::Si),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Iec => "iec",
+            Self::Si => "si",
+        }
+    }
+
+    fn step(self) -> f64 {
+        match self {
+            Self::Iec => 1024.0,
+            Self::Si => 1000.0,
+        }
+    }
+
+    fn units(self) -> [&'static str; 6] {
+        match self {
+            Self::Iec => ["B", "KiB", "MiB", "GiB", "TiB", "PiB"],
+            Self::Si => ["B", "KB", "MB", "GB", "TB", "PB"],
+        }
+    }
+}
+
+/// 速率以比特 (Mbps) 或字节 (MiB/s) 显示, 比特总是以 1000 换算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    Bits,
+    Bytes,
+}
+
+impl RateUnit {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.to_lowercase().as_str() {
+            "bit" | "bits" | "bps" => Some(Self::Bits),
+            "byte" | "bytes" | "b/s" => Some(Self::Bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Bits => "bit",
+            Self::Bytes => "byte",
+        }
+    }
+}
+
+/// 每个用户的单位与数字格式偏好
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Units {
+    pub base: SizeBase,
+    pub rate: RateUnit,
+    pub precision: usize,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            base: SizeBase::Iec,
+            rate: RateUnit::Bits,
+            precision: 2,
+        }
+    }
+}
+
+impl Units {
+    /// 自动选择单位显示字节数, 例如 `1.50 GiB`
+    pub fn bytes(&self, bytes: u64) -> String {
+        self.scale(bytes as f64, self.base.step(), &self.base.units())
+    }
+
+    /// 以指定的幂次显示字节数, 0 为 B, 3 为 GiB 或 GB
+    pub fn bytes_in(&self, bytes: u64, power: usize) -> String {
+        let units = self.base.units();
+        let power = power.min(units.len() - 1);
+        let divisor = self
+            .base
+            .step()
+            .powi(i32::try_from(power).unwrap_or_default());
+        format!("{} {}", self.number(bytes as f64 / divisor), units[power])
+    }
+
+    /// 自动选择单位显示速率, `bytes_per_second` 以 B/s 为单位
+    pub fn rate(&self, bytes_per_second: f64) -> String {
+        match self.rate {
+            RateUnit::Bits => self.scale(
+                bytes_per_second * 8.0,
+                1000.0,
+                &["bps", "Kbps", "Mbps", "Gbps", "Tbps"],
+            ),
+            RateUnit::Bytes => self.scale(
+                bytes_per_second,
+                self.base.step(),
+                &self.base.units().map(|unit| match unit {
+                    "B" => "B/s",
+                    "KiB" => "KiB/s",
+                    "MiB" => "MiB/s",
+                    "GiB" => "GiB/s",
+                    "TiB" => "TiB/s",
+                    "KB" => "KB/s",
+                    "MB" => "MB/s",
+                    "GB" => "GB/s",
+                    "TB" => "TB/s",
+                    _ => "PB/s",
+                }),
+            ),
+        }
+    }
+
+    pub fn number(&self, value: f64) -> String {
+        format!("{value:.*}", self.precision)
+    }
+
+    pub fn percent(&self, value: f64) -> String {
+        format!("{}%", self.number(value))
+    }
+
+    fn scale(&self, mut value: f64, step: f64, units: &[&str]) -> String {
+        let mut unit = 0;
+
+        while value >= step && unit < units.len() - 1 {
+            value /= step;
+            unit += 1;
+        }
+
+        format!("{} {}", self.number(value), units[unit])
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "容量: {} ({})\n速率: {} ({})\n小数位数: {}",
+            self.base.as_str(),
+            self.bytes(1_610_612_736),
+            self.rate.as_str(),
+            self.rate(12_500_000.0),
+            self.precision
+        )
+    }
+}
+
+/// 用户的单位偏好, 未设置时使用默认值
+pub async fn load_units(telegram_id: i64) -> Result<Units, ErrorString> {
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let Some((base, rate, precision)) = query_unit_setting(db_pool, telegram_id).await? else {
+        return Ok(Units::default());
+    };
+
+    let default = Units::default();
+    Ok(Units {
+        base: SizeBase::parse(&base).unwrap_or(default.base),
+        rate: RateUnit::parse(&rate).unwrap_or(default.rate),
+        precision: usize::try_from(precision)
+            .unwrap_or(default.precision)
+            .min(MAX_PRECISION),
+    })
+}
+
+/// `/units [iec|si] [bit|byte] [0-4]` 或 `/units reset`
+pub async fn units(telegram_id: i64, args: &[String]) -> Result<String, ErrorString> {
+    let usage = "用法:\n/units - 查看单位设置\n/units iec|si - 容量以 1024 (KiB) 或 1000 (KB) 换算\n/units bit|byte - 速率以 Mbps 或 MiB/s 显示\n/units 0-4 - 小数位数\n/units reset - 恢复默认\n\n可同时设置多项, 例如 /units si byte 1";
+
+    let db_pool = DB_POOL
+        .get()
+        .unwrap_or_else(|| panic!("数据库连接池未初始化"));
+
+    let mut units = load_units(telegram_id).await?;
+
+    if args.is_empty() {
+        return Ok(format!("当前单位设置:\n{}", units.describe()));
+    }
+
+    if args.len() == 1 && args[0] == "reset" {
+        delete_unit_setting(db_pool, telegram_id).await?;
+        return Ok(format!(
+            "已恢复默认单位设置:\n{}",
+            Units::default().describe()
+        ));
+    }
+
+    for arg in args {
+        if let Some(base) = SizeBase::parse(arg) {
+            units.base = base;
+        } else if let Some(rate) = RateUnit::parse(arg) {
+            units.rate = rate;
+        } else if let Some(precision) = arg
+            .parse::<usize>()
+            .ok()
+            .filter(|precision| *precision <= MAX_PRECISION)
+        {
+            units.precision = precision;
+        } else {
+            return Err(format!("无法识别的参数: {arg}\n\n{usage}"));
+        }
+    }
+
+    upsert_unit_setting(
+        db_pool,
+        telegram_id,
+        units.base.as_str(),
+        units.rate.as_str(),
+        i64::try_from(units.precision).unwrap_or_default(),
+    )
+    .await?;
+
+    Ok(format!("已保存单位设置:\n{}", units.describe()))
+}